@@ -0,0 +1,367 @@
+use proc_macro2::TokenStream;
+use syn::{Data, DeriveInput, Fields, FieldsNamed, Generics, Ident, Type, Visibility};
+
+use crate::record::{camel_case, members};
+
+/// Adds a `T: bound` predicate for every field type to a copy of `generics`
+fn bound_fields<'a>(
+    generics: &Generics,
+    types: impl IntoIterator<Item = &'a Type>,
+    bound: TokenStream,
+) -> Generics {
+    let mut generics = generics.clone();
+
+    if !generics.params.is_empty() {
+        let where_clause = generics.make_where_clause();
+        for ty in types {
+            where_clause.predicates.push(parse_quote!(#ty: #bound));
+        }
+    }
+
+    generics
+}
+
+/// Builds the pattern used to bind every field of a struct or variant to `__field_N`
+fn bind_fields(fields: &Fields) -> (TokenStream, Vec<Ident>) {
+    let bindings = (0..fields.len())
+        .map(|index| format_ident!("__field_{}", index))
+        .collect::<Vec<_>>();
+
+    let members = members(fields);
+    let pattern = match fields {
+        Fields::Unit => quote!(),
+        _ => quote!({ #(#members: #bindings),* }),
+    };
+
+    (pattern, bindings)
+}
+
+/// Returns the type used to tag enum variants, which is always wide enough to hold every variant index
+fn tag_type(variants: usize) -> TokenStream {
+    if variants <= u8::MAX as usize + 1 {
+        quote!(u8)
+    } else {
+        quote!(u16)
+    }
+}
+
+/// Generates a `{Name}Prefix<N>` builder holding the encoding of the first `N` fields of a struct,
+/// which is a valid `PrefixKey` of the struct itself and of the one-tuple holding it
+fn prefix_builder(vis: &Visibility, ident: &Ident, fields: &FieldsNamed) -> TokenStream {
+    let prefix = format_ident!("{}Prefix", ident);
+    let doc = format!(
+        "A prefix of a [`{}`] key, built by supplying its fields in declaration order",
+        ident
+    );
+
+    let methods = fields.named.iter().enumerate().map(|(index, field)| {
+        let name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let next = index + 1;
+
+        let (receiver, bytes) = if index == 0 {
+            (quote!(), quote!(::std::vec::Vec::new()))
+        } else {
+            (quote!(self,), quote!(self.0))
+        };
+
+        quote! {
+            impl #prefix<#index> {
+                #vis fn #name(#receiver #name: #ty) -> #prefix<#next> {
+                    let mut bytes = #bytes;
+                    bytes.extend_from_slice(::std::convert::AsRef::<[u8]>::as_ref(&::bobsled::EncodeKey::encode(&#name)));
+
+                    #prefix(bytes)
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        #vis struct #prefix<const N: usize>(::std::vec::Vec<u8>);
+
+        #(#methods)*
+
+        impl<const N: usize> ::bobsled::EncodeKey for #prefix<N> {
+            type Bytes = ::std::vec::Vec<u8>;
+
+            fn encode(&self) -> Self::Bytes {
+                self.0.clone()
+            }
+        }
+
+        impl<const N: usize> ::bobsled::PrefixKey<#ident> for #prefix<N> {}
+
+        // Derived records always key by a tuple, so a lone struct key is wrapped in a one-tuple
+        impl<const N: usize> ::bobsled::PrefixKey<(#ident,)> for #prefix<N> {}
+    }
+}
+
+pub(crate) fn derive_encode_key(input: DeriveInput) -> syn::Result<TokenStream> {
+    let DeriveInput {
+        vis,
+        ident,
+        generics,
+        data,
+        ..
+    } = input;
+
+    let encode = quote!(::bobsled::EncodeKey);
+    let mut prefix = quote!();
+
+    let (types, body) = match data {
+        Data::Struct(data) => {
+            if let Fields::Named(fields) = &data.fields {
+                if generics.params.is_empty() {
+                    prefix = prefix_builder(&vis, &ident, fields);
+                }
+            }
+
+            let (pattern, bindings) = bind_fields(&data.fields);
+            let types = data.fields.iter().map(|f| f.ty.clone()).collect::<Vec<_>>();
+
+            let body = quote! {
+                let Self #pattern = self;
+
+                #(
+                    vec.extend_from_slice(::std::convert::AsRef::<[u8]>::as_ref(&#encode::encode(#bindings)));
+                )*
+            };
+
+            (types, body)
+        }
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "EncodeKey cannot be derived on empty enums",
+                ));
+            }
+
+            let tag_type = tag_type(data.variants.len());
+            let types = data
+                .variants
+                .iter()
+                .flat_map(|v| v.fields.iter().map(|f| f.ty.clone()))
+                .collect::<Vec<_>>();
+
+            let arms = data.variants.iter().enumerate().map(|(tag, variant)| {
+                let name = &variant.ident;
+                let (pattern, bindings) = bind_fields(&variant.fields);
+                let tag = tag as u16;
+
+                quote! {
+                    Self::#name #pattern => {
+                        vec.extend_from_slice(&(#tag as #tag_type).to_be_bytes());
+
+                        #(
+                            vec.extend_from_slice(::std::convert::AsRef::<[u8]>::as_ref(&#encode::encode(#bindings)));
+                        )*
+                    }
+                }
+            });
+
+            let body = quote! {
+                match self {
+                    #(#arms)*
+                }
+            };
+
+            (types, body)
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                ident.span(),
+                "EncodeKey cannot be derived on unions",
+            ))
+        }
+    };
+
+    let generics = bound_fields(&generics, &types, encode.clone());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #encode for #ident #ty_generics #where_clause {
+            type Bytes = ::std::vec::Vec<u8>;
+
+            fn encode(&self) -> Self::Bytes {
+                let mut vec = ::std::vec::Vec::new();
+
+                #body
+
+                vec
+            }
+        }
+
+        #prefix
+    })
+}
+
+pub(crate) fn derive_decode_key(input: DeriveInput) -> syn::Result<TokenStream> {
+    let DeriveInput {
+        vis,
+        ident,
+        generics,
+        data,
+        ..
+    } = input;
+
+    let decode = quote!(::bobsled::DecodeKey);
+    let error = format_ident!("{}DecodeError", ident);
+    let error_doc = format!("Error produced when decoding a [`{}`] key", ident);
+
+    // Decodes each field in order, binding them to `__field_N` and returning the matching error variant on failure
+    let decode_fields = |fields: &Fields, variants: &[Ident]| {
+        let (_, bindings) = bind_fields(fields);
+        let types = fields.iter().map(|f| &f.ty);
+
+        quote! {
+            #(
+                let (#bindings, bytes) = match <#types as #decode>::try_decode(bytes) {
+                    ::std::result::Result::Ok(x) => x,
+                    ::std::result::Result::Err(err) => return ::std::result::Result::Err(#error::#variants(err)),
+                };
+            )*
+        }
+    };
+
+    let mut types = Vec::<Type>::new();
+    let mut variants = Vec::<Ident>::new();
+    let mut extra_variants = quote!();
+    let mut extra_display = quote!();
+    let mut extra_debug = quote!();
+
+    let body = match data {
+        Data::Struct(data) => {
+            let field_variants = members(&data.fields)
+                .iter()
+                .map(|member| format_ident!("Decode{}Error", camel_case(member)))
+                .collect::<Vec<_>>();
+
+            types.extend(data.fields.iter().map(|f| f.ty.clone()));
+            variants.extend(field_variants.iter().cloned());
+
+            let (pattern, _) = bind_fields(&data.fields);
+            let decode = decode_fields(&data.fields, &field_variants);
+
+            quote! {
+                #decode
+
+                ::std::result::Result::Ok((Self #pattern, bytes))
+            }
+        }
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "DecodeKey cannot be derived on empty enums",
+                ));
+            }
+
+            let tag_type = tag_type(data.variants.len());
+
+            let arms = data
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(tag, variant)| {
+                    let name = &variant.ident;
+                    let field_variants = members(&variant.fields)
+                        .iter()
+                        .map(|member| format_ident!("Decode{}{}Error", name, camel_case(member)))
+                        .collect::<Vec<_>>();
+
+                    types.extend(variant.fields.iter().map(|f| f.ty.clone()));
+                    variants.extend(field_variants.iter().cloned());
+
+                    let (pattern, _) = bind_fields(&variant.fields);
+                    let decode = decode_fields(&variant.fields, &field_variants);
+                    let tag = tag as u16;
+
+                    quote! {
+                        #tag => {
+                            #decode
+
+                            ::std::result::Result::Ok((Self::#name #pattern, bytes))
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            extra_variants = quote! {
+                DataTooShort(::bobsled::DataTooShort),
+                UnknownVariant(#tag_type),
+            };
+            extra_display = quote! {
+                Self::DataTooShort(ref err) => ::std::fmt::Display::fmt(err, f),
+                Self::UnknownVariant(tag) => write!(f, "Unknown variant tag {} for {}", tag, stringify!(#ident)),
+            };
+            extra_debug = quote! {
+                Self::DataTooShort(ref err) => f.debug_tuple("DataTooShort").field(err).finish(),
+                Self::UnknownVariant(ref tag) => f.debug_tuple("UnknownVariant").field(tag).finish(),
+            };
+
+            quote! {
+                let (tag, bytes) = match <#tag_type as #decode>::try_decode(bytes) {
+                    ::std::result::Result::Ok(x) => x,
+                    ::std::result::Result::Err(err) => return ::std::result::Result::Err(#error::DataTooShort(err)),
+                };
+
+                match tag as u16 {
+                    #(#arms)*
+                    _ => ::std::result::Result::Err(#error::UnknownVariant(tag)),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                ident.span(),
+                "DecodeKey cannot be derived on unions",
+            ))
+        }
+    };
+
+    let generics = bound_fields(&generics, &types, decode.clone());
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let variant_names = variants.iter().map(|v| v.to_string());
+
+    // Debug is implemented by hand, since deriving it would require every generic parameter to be Debug
+    Ok(quote! {
+        #[doc = #error_doc]
+        #[allow(clippy::enum_variant_names)]
+        #vis enum #error #impl_generics #where_clause {
+            #extra_variants
+            #(#variants(<#types as #decode>::Error)),*
+        }
+
+        impl #impl_generics ::std::fmt::Debug for #error #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match *self {
+                    #extra_debug
+                    #(Self::#variants(ref err) => f.debug_tuple(#variant_names).field(err).finish(),)*
+                }
+            }
+        }
+
+        impl #impl_generics ::std::fmt::Display for #error #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match *self {
+                    #extra_display
+                    #(Self::#variants(ref err) => ::std::fmt::Display::fmt(err, f),)*
+                }
+            }
+        }
+
+        impl #impl_generics ::std::error::Error for #error #ty_generics #where_clause {}
+
+        impl #impl_generics #decode for #ident #ty_generics #where_clause {
+            type Error = #error #ty_generics;
+
+            fn try_decode(bytes: &[u8]) -> ::std::result::Result<(Self, &[u8]), Self::Error> {
+                #body
+            }
+        }
+    })
+}
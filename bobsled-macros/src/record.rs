@@ -0,0 +1,291 @@
+use proc_macro2::{Span, TokenStream};
+use syn::{
+    Attribute, Data, DeriveInput, Field, Fields, Ident, Lit, LitByteStr, Member, Meta, NestedMeta,
+    Path,
+};
+
+/// Returns whether a field has been tagged with `#[bobsled(key)]`
+fn is_key_field(field: &Field) -> syn::Result<bool> {
+    let mut is_key = false;
+
+    for attr in field.attrs.iter().filter(|a| a.path.is_ident("bobsled")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected #[bobsled(...)]")),
+        };
+
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("key") => is_key = true,
+                nested => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "unsupported bobsled attribute",
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(is_key)
+}
+
+/// Returns the table set on a record with `#[bobsled(table = "...")]`, if any, and the indexes declared with
+/// `#[bobsled(index(...))]`
+fn record_attrs(attrs: &[Attribute]) -> syn::Result<(Option<LitByteStr>, Vec<Path>)> {
+    let mut table = None;
+    let mut indexes = Vec::new();
+
+    for attr in attrs.iter().filter(|a| a.path.is_ident("bobsled")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected #[bobsled(...)]")),
+        };
+
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(meta)) if meta.path.is_ident("table") => {
+                    table = match meta.lit {
+                        Lit::Str(lit) => Some(LitByteStr::new(lit.value().as_bytes(), lit.span())),
+                        Lit::ByteStr(lit) => Some(lit),
+                        lit => {
+                            return Err(syn::Error::new_spanned(
+                                lit,
+                                "expected a string or byte string",
+                            ))
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("index") => {
+                    for nested in list.nested {
+                        match nested {
+                            NestedMeta::Meta(Meta::Path(path)) => indexes.push(path),
+                            nested => {
+                                return Err(syn::Error::new_spanned(
+                                    nested,
+                                    "expected an index type",
+                                ))
+                            }
+                        }
+                    }
+                }
+                nested => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "unsupported bobsled attribute",
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok((table, indexes))
+}
+
+/// Converts a field member into the UpperCamelCase fragment used to name error variants
+pub(crate) fn camel_case(member: &Member) -> String {
+    let name = match member {
+        Member::Named(ident) => ident.to_string(),
+        Member::Unnamed(index) => return index.index.to_string(),
+    };
+
+    name.trim_start_matches("r#")
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the members of a set of fields, in declaration order
+pub(crate) fn members(fields: &Fields) -> Vec<Member> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        })
+        .collect()
+}
+
+pub(crate) fn derive_record(input: DeriveInput) -> syn::Result<TokenStream> {
+    let DeriveInput {
+        attrs,
+        vis,
+        ident,
+        generics,
+        data,
+    } = input;
+
+    let (table, indexes) = record_attrs(&attrs)?;
+    if let (None, [index, ..]) = (&table, indexes.as_slice()) {
+        return Err(syn::Error::new_spanned(
+            index,
+            "records with indexes need a table, so that scans don't visit their index entries",
+        ));
+    }
+    let table = match table {
+        Some(table) => quote!(const TABLE: &'static [u8] = #table;),
+        None => quote!(),
+    };
+    let indexes = match indexes.as_slice() {
+        [] => quote!(),
+        indexes => quote! {
+            fn indexes() -> ::std::vec::Vec<::bobsled::IndexDef<Self>> {
+                ::std::vec![#(::bobsled::IndexDef::of::<#indexes>()),*]
+            }
+        },
+    };
+
+    let fields = match data {
+        Data::Struct(data) => data.fields,
+        _ => {
+            return Err(syn::Error::new(
+                ident.span(),
+                "Record can only be derived on structs",
+            ))
+        }
+    };
+
+    let mut key_fields = Vec::new();
+    let mut value_fields = Vec::new();
+    for (field, member) in fields.iter().zip(members(&fields)) {
+        if is_key_field(field)? {
+            key_fields.push((field, member));
+        } else {
+            value_fields.push((field, member));
+        }
+    }
+
+    let encode_error = format_ident!("{}EncodeError", ident);
+    let decode_error = format_ident!("{}DecodeError", ident);
+
+    let key_types = key_fields.iter().map(|(field, _)| &field.ty);
+    let key_members = key_fields
+        .iter()
+        .map(|(_, member)| member)
+        .collect::<Vec<_>>();
+    let key_bindings = (0..key_fields.len())
+        .map(|index| format_ident!("__key_{}", index))
+        .collect::<Vec<_>>();
+
+    let value_members = value_fields
+        .iter()
+        .map(|(_, member)| member)
+        .collect::<Vec<_>>();
+    let value_bindings = (0..value_fields.len())
+        .map(|index| format_ident!("__value_{}", index))
+        .collect::<Vec<_>>();
+    let encode_variants = value_fields
+        .iter()
+        .map(|(_, member)| format_ident!("Encode{}Error", camel_case(member)))
+        .collect::<Vec<_>>();
+    let decode_variants = value_fields
+        .iter()
+        .map(|(_, member)| format_ident!("Decode{}Error", camel_case(member)))
+        .collect::<Vec<_>>();
+
+    let encode_doc = format!("Error produced when encoding a [`{}`] record", ident);
+    let decode_doc = format!("Error produced when decoding a [`{}`] record", ident);
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let bincode = quote!(::bobsled::bincode);
+    let value = Ident::new("__value", Span::call_site());
+    let options = Ident::new("__options", Span::call_site());
+
+    Ok(quote! {
+        #[doc = #encode_doc]
+        #[derive(Debug)]
+        #[allow(clippy::enum_variant_names)]
+        #vis enum #encode_error {
+            #(#encode_variants(#bincode::Error)),*
+        }
+
+        impl ::std::fmt::Display for #encode_error {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match *self {
+                    #(Self::#encode_variants(ref err) => ::std::fmt::Display::fmt(err, f)),*
+                }
+            }
+        }
+
+        impl ::std::error::Error for #encode_error {}
+
+        #[doc = #decode_doc]
+        #[derive(Debug)]
+        #[allow(clippy::enum_variant_names)]
+        #vis enum #decode_error {
+            #(#decode_variants(#bincode::Error)),*
+        }
+
+        impl ::std::fmt::Display for #decode_error {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match *self {
+                    #(Self::#decode_variants(ref err) => ::std::fmt::Display::fmt(err, f)),*
+                }
+            }
+        }
+
+        impl ::std::error::Error for #decode_error {}
+
+        impl #impl_generics ::bobsled::Record for #ident #ty_generics #where_clause {
+            type Key = (#(#key_types,)*);
+            type EncodeError = #encode_error;
+            type DecodeError = #decode_error;
+
+            #table
+
+            fn try_encode(&self) -> ::std::result::Result<(Self::Key, ::std::vec::Vec<u8>), Self::EncodeError> {
+                #[allow(unused_mut)]
+                let mut #value = ::std::vec::Vec::new();
+
+                #(
+                    if let ::std::result::Result::Err(err) = #bincode::serialize_into(&mut #value, &self.#value_members) {
+                        return ::std::result::Result::Err(#encode_error::#encode_variants(err));
+                    }
+                )*
+
+                ::std::result::Result::Ok((
+                    (#(::std::clone::Clone::clone(&self.#key_members),)*),
+                    #value,
+                ))
+            }
+
+            fn try_decode(key: Self::Key, #value: &[u8]) -> ::std::result::Result<Self, Self::DecodeError> {
+                #[allow(unused_mut, unused_variables)]
+                let mut #value = #value;
+                let (#(#key_bindings,)*) = key;
+
+                // Limiting reads to the value's length keeps corrupt length headers from allocating huge buffers
+                #[allow(unused_variables)]
+                let #options = #bincode::Options::with_limit(
+                    #bincode::Options::allow_trailing_bytes(
+                        #bincode::Options::with_fixint_encoding(#bincode::DefaultOptions::new()),
+                    ),
+                    #value.len() as u64,
+                );
+
+                #(
+                    let #value_bindings = match #bincode::Options::deserialize_from(#options, &mut #value) {
+                        ::std::result::Result::Ok(field) => field,
+                        ::std::result::Result::Err(err) => return ::std::result::Result::Err(#decode_error::#decode_variants(err)),
+                    };
+                )*
+
+                ::std::result::Result::Ok(Self {
+                    #(#key_members: #key_bindings,)*
+                    #(#value_members: #value_bindings,)*
+                })
+            }
+
+            #indexes
+        }
+    })
+}
@@ -1,5 +1,5 @@
 use std::{
-    convert::{Infallible, TryInto},
+    convert::Infallible,
     ffi::{OsStr, OsString},
     ops::Deref,
     path::{Path, PathBuf},
@@ -75,7 +75,7 @@ impl<'a> EncodeKey for GreedyKey<&'a str> {
     }
 }
 
-impl<'a> PrefixKey<GreedyKey<String>> for GreedyKey<&'a str> {}
+impl PrefixKey<GreedyKey<String>> for GreedyKey<&str> {}
 
 impl EncodeKey for GreedyKey<OsString> {
     type Bytes = Vec<u8>;
@@ -110,7 +110,7 @@ impl DecodeKey for GreedyKey<OsString> {
 
     #[cfg(windows)]
     fn try_decode(bytes: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
-        use std::os::windows::ffi::OsStringExt;
+        use std::{convert::TryInto, os::windows::ffi::OsStringExt};
 
         let wide = bytes
             .chunks_exact(std::mem::size_of::<u16>())
@@ -145,7 +145,7 @@ impl<'a> EncodeKey for GreedyKey<&'a OsStr> {
     }
 }
 
-impl<'a> PrefixKey<GreedyKey<OsString>> for GreedyKey<&'a OsStr> {}
+impl PrefixKey<GreedyKey<OsString>> for GreedyKey<&OsStr> {}
 
 impl EncodeKey for GreedyKey<PathBuf> {
     type Bytes = Vec<u8>;
@@ -175,4 +175,4 @@ impl<'a> EncodeKey for GreedyKey<&'a Path> {
     }
 }
 
-impl<'a> PrefixKey<GreedyKey<PathBuf>> for GreedyKey<&'a Path> {}
+impl PrefixKey<GreedyKey<PathBuf>> for GreedyKey<&Path> {}
@@ -1,5 +1,5 @@
 use std::{
-    convert::{Infallible, TryInto},
+    convert::{Infallible, TryFrom, TryInto},
     ffi::{CStr, CString, FromBytesWithNulError, OsStr, OsString},
     fmt::{Debug, Display},
     path::{Path, PathBuf},
@@ -16,6 +16,33 @@ pub trait EncodeKey {
     fn encode(&self) -> Self::Bytes;
 }
 
+/// Size of the length header written before variable-length data (strings, slices, etc.).
+///
+/// Lengths are always written as a big-endian `u64`, regardless of the platform's pointer width, so data written
+/// on a 64-bit host can be read on a 32-bit host and vice versa. This is byte-for-byte identical to the `usize`
+/// header previously written by 64-bit hosts, so existing data from those hosts decodes unchanged.
+pub const LENGTH_HEADER_SIZE: usize = std::mem::size_of::<u64>();
+
+fn encode_length(len: usize) -> [u8; LENGTH_HEADER_SIZE] {
+    (len as u64).to_be_bytes()
+}
+
+/// Reads a length header, returning the length and any remaining bytes
+fn decode_length(bytes: &[u8]) -> Result<(usize, &[u8]), DataTooShort> {
+    if bytes.len() < LENGTH_HEADER_SIZE {
+        return Err(DataTooShort {
+            expected: LENGTH_HEADER_SIZE,
+            actual: bytes.len(),
+        });
+    }
+
+    let (len, bytes) = bytes.split_at(LENGTH_HEADER_SIZE);
+    let len = u64::from_be_bytes(len.try_into().unwrap());
+
+    // A length that doesn't fit in a usize can't possibly be followed by enough data
+    Ok((usize::try_from(len).unwrap_or(usize::MAX), bytes))
+}
+
 /// Implement encode for one or more container types
 macro_rules! impl_container_encode {
     ($($type:ty),+) => {$(
@@ -52,16 +79,16 @@ impl EncodeKey for str {
 
     fn encode(&self) -> Self::Bytes {
         let bytes = self.as_bytes();
-        let mut vec = Vec::with_capacity(std::mem::size_of::<usize>() + bytes.len());
+        let mut vec = Vec::with_capacity(LENGTH_HEADER_SIZE + bytes.len());
 
-        vec.extend_from_slice(&bytes.len().to_be_bytes());
+        vec.extend_from_slice(&encode_length(bytes.len()));
         vec.extend_from_slice(bytes);
 
         vec
     }
 }
 
-impl<'a> EncodeKey for &'a str {
+impl EncodeKey for &str {
     type Bytes = Vec<u8>;
 
     fn encode(&self) -> Self::Bytes {
@@ -106,7 +133,7 @@ where
     fn encode(&self) -> Self::Bytes {
         let mut vec = Vec::new();
 
-        vec.extend_from_slice(&self.len().to_be_bytes());
+        vec.extend_from_slice(&encode_length(self.len()));
         for item in self {
             vec.extend_from_slice(item.encode().as_ref());
         }
@@ -212,15 +239,7 @@ impl DecodeKey for String {
     type Error = StringDecodeError;
 
     fn try_decode(bytes: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
-        if bytes.len() < std::mem::size_of::<usize>() {
-            return Err(StringDecodeError::DataTooShort(DataTooShort {
-                expected: std::mem::size_of::<usize>(),
-                actual: bytes.len(),
-            }));
-        }
-
-        let (len, bytes) = bytes.split_at(std::mem::size_of::<usize>());
-        let len = usize::from_be_bytes(len.try_into().unwrap());
+        let (len, bytes) = decode_length(bytes)?;
 
         if bytes.len() < len {
             return Err(StringDecodeError::DataTooShort(DataTooShort {
@@ -281,17 +300,10 @@ impl<T: DecodeKey> DecodeKey for Vec<T> {
     type Error = VecDecodeError<T::Error>;
 
     fn try_decode(bytes: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
-        if bytes.len() < std::mem::size_of::<usize>() {
-            return Err(VecDecodeError::HeaderError(DataTooShort {
-                expected: std::mem::size_of::<usize>(),
-                actual: bytes.len(),
-            }));
-        }
+        let (len, mut bytes) = decode_length(bytes).map_err(VecDecodeError::HeaderError)?;
 
-        let (len, mut bytes) = bytes.split_at(std::mem::size_of::<usize>());
-        let len = usize::from_be_bytes(len.try_into().unwrap());
-
-        let mut buf = Vec::with_capacity(len);
+        // Don't trust a possibly corrupt header to size the allocation up front
+        let mut buf = Vec::with_capacity(len.min(bytes.len()));
         while buf.len() < len {
             match T::try_decode(bytes) {
                 Ok((elem, suffix)) => {
@@ -461,6 +473,7 @@ macro_rules! impl_tuple_key {
         const _: () = {
             paste! {
                 #[derive(Debug)]
+                #[allow(clippy::enum_variant_names)]
                 pub enum [< TupleDecodeError $($gen)+ >]<$($gen,)+> where $($gen: ::std::error::Error),+ {
                     $(
                         [< Decode $gen Error >]($gen)
@@ -537,5 +550,10 @@ mod greedy;
 #[cfg(feature = "greedy")]
 pub use greedy::*;
 
+#[cfg(feature = "ordered")]
+mod ordered;
+#[cfg(feature = "ordered")]
+pub use ordered::*;
+
 #[cfg(feature = "widestring")]
 mod widestring;
@@ -0,0 +1,206 @@
+use std::{fmt::Display, ops::Deref, str::Utf8Error};
+
+use crate::{DecodeKey, EncodeKey, PrefixKey};
+
+const ESCAPE: u8 = 0x00;
+const ESCAPED_NUL: u8 = 0xFF;
+const TERMINATOR: u8 = 0x01;
+
+/// A key wrapper for variable-length data that encodes so that byte order matches the order of the wrapped value.
+///
+/// Rather than writing a length header, every `0x00` byte in the data is escaped as `0x00 0xFF`, and the data is
+/// terminated by `0x00 0x01` (much like FoundationDB's tuple layer). This makes range scans over string keys behave
+/// as expected (e.g. `Ordered("a")..Ordered("b")`), while still composing inside of tuples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ordered<T>(pub T)
+where
+    Self: EncodeKey;
+
+impl<T> From<T> for Ordered<T>
+where
+    Self: EncodeKey,
+{
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Ordered<T>
+where
+    Self: EncodeKey,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Appends `bytes` to `buf`, escaping any nul bytes, but without writing the terminator
+pub(crate) fn escape_into(buf: &mut Vec<u8>, bytes: &[u8]) {
+    for &b in bytes {
+        buf.push(b);
+
+        if b == ESCAPE {
+            buf.push(ESCAPED_NUL);
+        }
+    }
+}
+
+/// Escapes and terminates `bytes`
+fn escape(bytes: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(bytes.len() + 2);
+
+    escape_into(&mut buf, bytes);
+    buf.extend_from_slice(&[ESCAPE, TERMINATOR]);
+
+    buf
+}
+
+/// Reads escaped data up to (and including) its terminator, returning the unescaped data and any remaining bytes
+fn unescape(bytes: &[u8]) -> Result<(Vec<u8>, &[u8]), OrderedDecodeError> {
+    let mut buf = Vec::new();
+    let mut iter = bytes.iter().enumerate();
+
+    while let Some((_, &b)) = iter.next() {
+        if b != ESCAPE {
+            buf.push(b);
+            continue;
+        }
+
+        match iter.next() {
+            Some((_, &ESCAPED_NUL)) => buf.push(ESCAPE),
+            Some((pos, &TERMINATOR)) => return Ok((buf, &bytes[pos + 1..])),
+            Some((_, &b)) => return Err(OrderedDecodeError::InvalidEscape(b)),
+            None => break,
+        }
+    }
+
+    Err(OrderedDecodeError::Unterminated)
+}
+
+#[derive(Debug)]
+pub enum OrderedDecodeError {
+    /// The data ended before the terminator was found
+    Unterminated,
+    /// A nul byte was followed by something other than an escape or a terminator
+    InvalidEscape(u8),
+    Utf8Error(Utf8Error),
+}
+
+impl From<Utf8Error> for OrderedDecodeError {
+    fn from(err: Utf8Error) -> Self {
+        Self::Utf8Error(err)
+    }
+}
+
+impl Display for OrderedDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unterminated => write!(f, "Expected data to be terminated by 0x00 0x01"),
+            Self::InvalidEscape(b) => write!(f, "Invalid escape sequence 0x00 {:#04x}", b),
+            Self::Utf8Error(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for OrderedDecodeError {}
+
+impl EncodeKey for Ordered<Vec<u8>> {
+    type Bytes = Vec<u8>;
+
+    fn encode(&self) -> Self::Bytes {
+        escape(&self.0)
+    }
+}
+
+impl DecodeKey for Ordered<Vec<u8>> {
+    type Error = OrderedDecodeError;
+
+    fn try_decode(bytes: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
+        unescape(bytes).map(|(data, bytes)| (Ordered(data), bytes))
+    }
+}
+
+impl EncodeKey for Ordered<&[u8]> {
+    type Bytes = Vec<u8>;
+
+    fn encode(&self) -> Self::Bytes {
+        escape(self.0)
+    }
+}
+
+impl PrefixKey<Ordered<Vec<u8>>> for Ordered<&[u8]> {}
+
+impl PrefixKey<(Ordered<Vec<u8>>,)> for Ordered<&[u8]> {}
+
+impl EncodeKey for Ordered<String> {
+    type Bytes = Vec<u8>;
+
+    fn encode(&self) -> Self::Bytes {
+        escape(self.0.as_bytes())
+    }
+}
+
+impl DecodeKey for Ordered<String> {
+    type Error = OrderedDecodeError;
+
+    fn try_decode(bytes: &[u8]) -> Result<(Self, &[u8]), Self::Error> {
+        let (data, bytes) = unescape(bytes)?;
+
+        match String::from_utf8(data) {
+            Ok(string) => Ok((Ordered(string), bytes)),
+            Err(err) => Err(err.utf8_error().into()),
+        }
+    }
+}
+
+impl EncodeKey for Ordered<&str> {
+    type Bytes = Vec<u8>;
+
+    fn encode(&self) -> Self::Bytes {
+        escape(self.0.as_bytes())
+    }
+}
+
+impl PrefixKey<Ordered<String>> for Ordered<&str> {}
+
+impl PrefixKey<(Ordered<String>,)> for Ordered<&str> {}
+
+/// Matches every [`Ordered<String>`] that starts with the given string, for use with prefix scans.
+///
+/// Also a valid prefix of any tuple starting with an [`Ordered<String>`], and can be used as the last element of a
+/// tuple prefix (e.g. `(user_id, StrPrefix("ab"))` for `(u64, Ordered<String>, u64)` keys).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrPrefix<'a>(pub &'a str);
+
+impl EncodeKey for StrPrefix<'_> {
+    type Bytes = Vec<u8>;
+
+    fn encode(&self) -> Self::Bytes {
+        let mut buf = Vec::with_capacity(self.0.len());
+        escape_into(&mut buf, self.0.as_bytes());
+
+        buf
+    }
+}
+
+impl PrefixKey<Ordered<String>> for StrPrefix<'_> {}
+
+macro_rules! impl_str_prefix {
+    () => {
+        impl PrefixKey<(Ordered<String>,)> for StrPrefix<'_> {}
+    };
+    ($head:ident $(, $tail:ident)*) => {
+        impl<$head $(, $tail)*> PrefixKey<(Ordered<String>, $head, $($tail,)*)> for StrPrefix<'_>
+        where
+            $head: EncodeKey,
+            $($tail: EncodeKey,)*
+        {
+        }
+
+        impl_str_prefix!($($tail),*);
+    };
+}
+
+impl_str_prefix!(B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z);
@@ -1,11 +1,12 @@
 #![forbid(unsafe_code)]
 
-use std::{fmt::Display, ops::RangeBounds};
+use std::{fmt::Display, iter::Rev, ops::RangeBounds};
 
 #[macro_use]
 extern crate paste;
 
-// pub use bobsled_macros::Record;
+pub use bincode;
+pub use bobsled_macros::{DecodeKey, EncodeKey, Record};
 
 mod key;
 pub use key::*;
@@ -36,27 +37,49 @@ pub trait Record: Sized {
     type EncodeError: std::error::Error;
     type DecodeError: std::error::Error;
 
+    /// Prepended to every key of this record type, so that many record types can share a single store without
+    /// colliding. Scans only ever visit records in the same table.
+    ///
+    /// No table should be a prefix of another table in the same store (fixed-length table ids are easiest), or scans
+    /// over the shorter one will also visit records from the longer one.
+    const TABLE: &'static [u8] = b"";
+
     fn try_encode(&self) -> Result<(Self::Key, Vec<u8>), Self::EncodeError>;
     fn try_decode(key: Self::Key, value: &[u8]) -> Result<Self, Self::DecodeError>;
 
+    /// The secondary indexes of this record type, whose entries are written alongside each record.
+    ///
+    /// Records with indexes need a [`Record::TABLE`], so that scans over them don't visit their index entries; writing
+    /// one without a table fails with [`WriteStoreError::NoTable`] (or [`CreateStoreError::NoTable`]).
+    ///
+    /// Entries are kept in sync by every write (a [`RecordBatch`] syncs them when it's applied) except writes to a sled
+    /// `Batch`, which can't see the records they replace and so leave those records' entries behind;
+    /// [`Record::scan_index`] skips those.
+    fn indexes() -> Vec<IndexDef<Self>> {
+        Vec::new()
+    }
+
     #[inline]
-    fn fetch<S: ReadStore<Self>>(
+    fn fetch<S: PointReadStore<Self>>(
         store: S,
         key: &Self::Key,
-    ) -> Result<
-        Option<Self>,
-        ReadStoreError<S::Error, <Self::Key as DecodeKey>::Error, Self::DecodeError>,
-    > {
+    ) -> Result<Option<Self>, RecordReadError<Self, S::Error>> {
         store.fetch(key)
     }
 
+    /// Checks whether a record with the given key exists, without decoding it
+    #[inline]
+    fn exists<S: PointReadStore<Self>>(store: S, key: &Self::Key) -> Result<bool, S::Error> {
+        store.exists(key)
+    }
+
     #[inline]
-    fn scan<S: ReadStore<Self>>(store: S) -> S::Iter {
+    fn scan<S: ScanStore<Self>>(store: S) -> S::Iter {
         store.scan()
     }
 
     #[inline]
-    fn scan_range<S: ReadStore<Self>, P: PrefixKey<Self::Key>>(
+    fn scan_range<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
         store: S,
         range: impl RangeBounds<P>,
     ) -> S::Iter {
@@ -64,10 +87,120 @@ pub trait Record: Sized {
     }
 
     #[inline]
-    fn scan_prefix<S: ReadStore<Self>, P: PrefixKey<Self::Key>>(store: S, prefix: &P) -> S::Iter {
+    fn scan_prefix<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(store: S, prefix: &P) -> S::Iter {
         store.scan_prefix(prefix)
     }
 
+    /// Like [`Record::scan`], but only decodes the keys of each record
+    #[inline]
+    fn scan_keys<S: ScanStore<Self>>(store: S) -> S::KeyIter {
+        store.scan_keys()
+    }
+
+    /// Like [`Record::scan_range`], but only decodes the keys of each record
+    #[inline]
+    fn scan_keys_range<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        range: impl RangeBounds<P>,
+    ) -> S::KeyIter {
+        store.scan_keys_range(range)
+    }
+
+    /// Like [`Record::scan_prefix`], but only decodes the keys of each record
+    #[inline]
+    fn scan_keys_prefix<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        prefix: &P,
+    ) -> S::KeyIter {
+        store.scan_keys_prefix(prefix)
+    }
+
+    /// Counts the records in `range`, without decoding them
+    #[inline]
+    fn count_range<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        range: impl RangeBounds<P>,
+    ) -> Result<usize, S::Error> {
+        store.count_range(range)
+    }
+
+    /// Counts the records starting with `prefix`, without decoding them
+    #[inline]
+    fn count_prefix<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        prefix: &P,
+    ) -> Result<usize, S::Error> {
+        store.count_prefix(prefix)
+    }
+
+    /// Estimates the size in bytes (keys and values) of the records in `range`
+    #[inline]
+    fn size_range<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        range: impl RangeBounds<P>,
+    ) -> Result<u64, S::Error> {
+        store.size_range(range)
+    }
+
+    /// Estimates the size in bytes (keys and values) of the records starting with `prefix`
+    #[inline]
+    fn size_prefix<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        prefix: &P,
+    ) -> Result<u64, S::Error> {
+        store.size_prefix(prefix)
+    }
+
+    /// Fetches a page of up to `limit` records in `range`, starting after the cursor returned with the previous page
+    #[inline]
+    fn scan_range_page<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        range: impl RangeBounds<P>,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<Self>, RecordReadError<Self, S::Error>> {
+        store.scan_range_page(range, after, limit)
+    }
+
+    /// Fetches a page of up to `limit` records starting with `prefix`, starting after the cursor returned with the
+    /// previous page
+    #[inline]
+    fn scan_prefix_page<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        prefix: &P,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<Self>, RecordReadError<Self, S::Error>> {
+        store.scan_prefix_page(prefix, after, limit)
+    }
+
+    /// Like [`Record::scan_range`], but in descending key order
+    #[inline]
+    fn scan_range_rev<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        range: impl RangeBounds<P>,
+    ) -> Rev<S::Iter> {
+        store.scan_range(range).rev()
+    }
+
+    /// Like [`Record::scan_prefix`], but in descending key order
+    #[inline]
+    fn scan_prefix_rev<S: ScanStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        prefix: &P,
+    ) -> Rev<S::Iter> {
+        store.scan_prefix(prefix).rev()
+    }
+
+    /// Scans the records whose key in the index `I` starts with `prefix`, in index key order
+    #[inline]
+    fn scan_index<I: Index<Self>, S: IndexStore<Self>>(
+        store: S,
+        prefix: &impl PrefixKey<I::Key>,
+    ) -> IndexIter<Self, I, S> {
+        store.scan_index::<I>(prefix)
+    }
+
     #[inline]
     fn persist<S: WriteStore<Self>>(
         &self,
@@ -76,11 +209,107 @@ pub trait Record: Sized {
         store.persist(self)
     }
 
+    /// Creates the record built by `f` from the next id of the store's [`Sequence`], and returns it.
+    /// The id should become (part of) the record's key.
+    ///
+    /// Like [`Record::create`], this fails with [`CreateStoreError::AlreadyExists`] rather than overwriting a record
+    /// that already has the key. Sled trees can't generate ids themselves, so outside of a transaction they're paired
+    /// with their database, as `(&db, &tree)`.
+    #[inline]
+    fn insert_auto<S>(
+        mut store: S,
+        f: impl FnOnce(u64) -> Self,
+    ) -> Result<Self, InsertAutoError<<S as CreateStore<Self>>::Error, Self::EncodeError>>
+    where
+        S: CreateStore<Self> + Sequence<Self, Error = <S as CreateStore<Self>>::Error>,
+    {
+        let record = match store.next_id() {
+            Ok(Ok(id)) => f(id),
+            Ok(Err(err)) => return Err(InsertAutoError::SequenceError(err)),
+            Err(err) => {
+                return Err(InsertAutoError::CreateError(CreateStoreError::StoreError(
+                    err,
+                )))
+            }
+        };
+
+        match store.create(&record) {
+            Ok(()) => Ok(record),
+            Err(err) => Err(InsertAutoError::CreateError(err)),
+        }
+    }
+
+    /// Atomically replaces the record stored under `key` with the result of `f`, which is passed the current record.
+    /// Returning `None` removes the record. `f` may be called more than once if the record is modified concurrently.
+    #[inline]
+    fn update<S: UpdateStore<Self>, F: FnMut(Option<Self>) -> Option<Self>>(
+        store: S,
+        key: &Self::Key,
+        f: F,
+    ) -> Result<Option<Self>, RecordUpdateError<Self, S::Error>> {
+        store.update(key, f)
+    }
+
+    /// Atomically replaces the record stored under `key` with `new`, but only if it currently matches `expected`.
+    /// On conflict, the current record is returned instead.
     #[inline]
-    fn remove<S: WriteStore<Self>>(
+    #[allow(clippy::type_complexity)]
+    fn compare_and_swap<S: CompareAndSwapStore<Self>>(
         store: S,
         key: &Self::Key,
-    ) -> Result<(), S::Error> {
+        expected: Option<&Self>,
+        new: Option<&Self>,
+    ) -> Result<Result<(), CompareAndSwapError<Self>>, RecordUpdateError<Self, S::Error>> {
+        store.compare_and_swap(key, expected, new)
+    }
+
+    /// Persists this record, failing with [`CreateStoreError::AlreadyExists`] if its key is already present
+    #[inline]
+    fn create<S: CreateStore<Self>>(
+        &self,
+        store: S,
+    ) -> Result<(), CreateStoreError<S::Error, Self::EncodeError>> {
+        store.create(self)
+    }
+
+    /// Persists this record, returning the record it replaced
+    #[inline]
+    fn replace<S: ReplaceStore<Self>>(
+        &self,
+        store: S,
+    ) -> Result<Option<Self>, RecordUpdateError<Self, S::Error>> {
+        store.replace(self)
+    }
+
+    /// Removes the record stored under `key`, returning it if it existed
+    #[inline]
+    fn take<S: ReplaceStore<Self>>(
+        store: S,
+        key: &Self::Key,
+    ) -> Result<Option<Self>, RecordReadError<Self, S::Error>> {
+        store.take(key)
+    }
+
+    #[inline]
+    fn remove<S: WriteStore<Self>>(store: S, key: &Self::Key) -> Result<(), S::Error> {
         store.remove(key)
     }
+
+    /// Removes every record in `range`, returning how many were removed
+    #[inline]
+    fn remove_range<S: RemoveRangeStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        range: impl RangeBounds<P>,
+    ) -> Result<usize, S::Error> {
+        store.remove_range(range)
+    }
+
+    /// Removes every record starting with `prefix`, returning how many were removed
+    #[inline]
+    fn remove_prefix<S: RemoveRangeStore<Self>, P: PrefixKey<Self::Key>>(
+        store: S,
+        prefix: &P,
+    ) -> Result<usize, S::Error> {
+        store.remove_prefix(prefix)
+    }
 }
@@ -0,0 +1,242 @@
+use std::convert::Infallible;
+
+use super::{
+    index::{Claim, Pending},
+    IndexWriteError, RawBatchStore, RawWriteStore, UniqueViolation,
+};
+
+/// A set of writes to records of any type, applied to a store all at once.
+///
+/// Records are added with [`Record::persist`](crate::Record::persist) and [`Record::remove`](crate::Record::remove),
+/// which encode them immediately; a record that fails to encode is never added, so applying the batch can't fail
+/// partway through due to an encode error. Later writes to the same key replace earlier ones.
+///
+/// Since a batch can't see the records it replaces, the index entries of the records written to it are only written
+/// once it's applied, along with removals of the entries of the records they replace. For the same reason, unique
+/// indexes are only checked once the batch is applied, which fails (writing nothing) if any of its records would
+/// share a unique index key with another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordBatch {
+    writes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    claims: Vec<Claim>,
+    pending: Vec<Pending>,
+    /// Set while a record is written to the batch, so its index writes are deferred
+    deferring: bool,
+}
+
+impl RecordBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Iterates over the raw writes in the order they were added, where a value of `None` is a removal.
+    ///
+    /// Index writes are only added by [`prepare`](Self::prepare).
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], Option<&[u8]>)> {
+        self.writes
+            .iter()
+            .map(|(key, value)| (key.as_slice(), value.as_deref()))
+    }
+
+    pub(crate) fn push(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+        self.writes.push((key, value));
+    }
+
+    pub(crate) fn claim(&mut self, claim: Claim) {
+        self.writes
+            .push((claim.key.clone(), Some(claim.owner.clone())));
+        self.claims.push(claim);
+    }
+
+    pub(crate) fn is_deferring(&self) -> bool {
+        self.deferring
+    }
+
+    pub(crate) fn defer(&mut self, pending: Pending) {
+        self.pending.push(pending);
+    }
+
+    /// Adds the index writes of the records written to the batch, reading the records they replace with `get`, then
+    /// checks that none of the unique index entries written by the batch belong to another record once it's applied,
+    /// whether that record is already stored or written by the same batch. Returns the batch to apply.
+    ///
+    /// Stores should call this from [`RawBatchStore::apply_batch`] before writing anything, as part of the same atomic
+    /// operation as the writes themselves.
+    pub fn prepare<V: AsRef<[u8]>, E>(
+        mut self,
+        mut get: impl FnMut(&[u8]) -> Result<Option<V>, E>,
+    ) -> Result<Result<RecordBatch, UniqueViolation>, E> {
+        self.resolve(&mut get)?;
+
+        Ok(self.check_claims(get)?.map(|()| self))
+    }
+
+    /// Adds the deferred index writes of the records written to the batch, reading the records they replace with
+    /// `get`. Every removal is added before any new entry, so unique entries can move between records.
+    fn resolve<V: AsRef<[u8]>, E>(
+        &mut self,
+        get: &mut impl FnMut(&[u8]) -> Result<Option<V>, E>,
+    ) -> Result<(), E> {
+        let pending = std::mem::take(&mut self.pending);
+        let mut writes = RecordBatch::new();
+
+        for (index, record) in pending.iter().enumerate() {
+            // A record written more than once only needs the entries of its final value
+            if pending[..index].iter().any(|other| other.key == record.key) {
+                continue;
+            }
+
+            let old = get(&record.key)?;
+            let new = self
+                .writes
+                .iter()
+                .rev()
+                .find(|(key, _)| *key == record.key)
+                .and_then(|(_, value)| value.as_deref());
+
+            (record.resolve)(
+                &record.key,
+                old.as_ref().map(AsRef::as_ref),
+                new,
+                &mut writes,
+            );
+        }
+
+        let (removals, additions): (Vec<_>, Vec<_>) = writes
+            .writes
+            .into_iter()
+            .partition(|(_, value)| value.is_none());
+        self.writes.extend(removals);
+        self.writes.extend(additions);
+        self.claims.extend(writes.claims);
+
+        Ok(())
+    }
+
+    /// Checks that none of the unique index entries written by the batch belong to another record once it's applied,
+    /// whether that record is already stored (read with `get`) or written by the same batch
+    fn check_claims<V: AsRef<[u8]>, E>(
+        &self,
+        mut get: impl FnMut(&[u8]) -> Result<Option<V>, E>,
+    ) -> Result<Result<(), UniqueViolation>, E> {
+        for claim in &self.claims {
+            let stored = get(&claim.key)?.map(|owner| owner.as_ref().to_owned());
+            let claimed = self
+                .claims
+                .iter()
+                .filter(|other| other.key == claim.key)
+                .map(|other| &other.owner);
+
+            for owner in stored.iter().chain(claimed) {
+                if *owner == claim.owner {
+                    continue;
+                }
+
+                let mut read = |key: &[u8]| self.read(key, &mut get);
+                if claim.is_held_by(&claim.owner, &mut read)?
+                    && claim.is_held_by(owner, &mut read)?
+                {
+                    return Ok(Err(UniqueViolation { index: claim.index }));
+                }
+            }
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// Reads the value `key` will have once the batch is applied, reading values it doesn't write with `get`
+    fn read<V: AsRef<[u8]>, E>(
+        &self,
+        key: &[u8],
+        get: impl FnOnce(&[u8]) -> Result<Option<V>, E>,
+    ) -> Result<Option<Vec<u8>>, E> {
+        match self.writes.iter().rev().find(|(k, _)| k.as_slice() == key) {
+            Some((_, value)) => Ok(value.clone()),
+            None => get(key).map(|value| value.map(|value| value.as_ref().to_owned())),
+        }
+    }
+
+    /// Runs the `f` passed to [`RawUpdateStore::update`](super::RawUpdateStore::update) against the current value of
+    /// `key`, read with `get`. Returns the batch of writes to apply, ending with the new value of `key`, so stores that
+    /// can read and write in one atomic operation only need to apply it.
+    pub fn prepare_update<V: AsRef<[u8]>, SE, E>(
+        key: &[u8],
+        get: impl FnOnce(&[u8]) -> Result<Option<V>, SE>,
+        f: impl FnOnce(Option<&[u8]>, &mut RecordBatch) -> Result<Option<Vec<u8>>, E>,
+    ) -> Result<Result<RecordBatch, E>, SE> {
+        let mut batch = RecordBatch::new();
+        let current = get(key)?;
+
+        let value = match f(current.as_ref().map(AsRef::as_ref), &mut batch) {
+            Ok(value) => value,
+            Err(err) => return Ok(Err(err)),
+        };
+
+        batch.push(key.into(), value);
+
+        Ok(Ok(batch))
+    }
+
+    /// Whether the batch has to read the store in [`prepare`](Self::prepare) before it can be applied
+    #[cfg(feature = "sled")]
+    pub(crate) fn needs_prepare(&self) -> bool {
+        !self.claims.is_empty() || !self.pending.is_empty()
+    }
+
+    /// Applies every write in the batch to `store` atomically, or fails with a [`UniqueViolation`] (writing nothing)
+    /// if it fails [`prepare`](Self::prepare)
+    pub fn apply<S: RawBatchStore>(
+        self,
+        store: S,
+    ) -> Result<Result<(), UniqueViolation>, S::Error> {
+        store.apply_batch(self)
+    }
+}
+
+impl IntoIterator for RecordBatch {
+    type Item = (Vec<u8>, Option<Vec<u8>>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.writes.into_iter()
+    }
+}
+
+impl RawWriteStore for &mut RecordBatch {
+    type Error = Infallible;
+
+    fn insert(self, key: &[u8], value: Vec<u8>) -> Result<(), Self::Error> {
+        self.writes.push((key.into(), Some(value)));
+
+        Ok(())
+    }
+
+    fn remove(self, key: &[u8]) -> Result<(), Self::Error> {
+        self.writes.push((key.into(), None));
+
+        Ok(())
+    }
+
+    fn write_with(
+        self,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+        mut f: impl FnMut(Option<&[u8]>, &mut RecordBatch),
+    ) -> Result<Result<(), IndexWriteError>, Self::Error> {
+        self.writes.push((key.into(), value));
+
+        self.deferring = true;
+        f(None, self);
+        self.deferring = false;
+
+        Ok(Ok(()))
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{btree_map, BTreeMap},
+    convert::Infallible,
+    ops::Bound,
+};
+
+use super::{
+    next_counter, sequence_key, RawBatchStore, RawExclusiveStore, RawRemoveRangeStore,
+    RawReplaceStore, RawScanStore, RawStore, RecordBatch, Sequence, SequenceError, StoreIter,
+    UniqueViolation,
+};
+use crate::Record;
+
+pub struct BTreeRange<'a> {
+    iter: btree_map::Range<'a, Vec<u8>, Vec<u8>>,
+}
+
+impl<'a> Iterator for BTreeRange<'a> {
+    type Item = Result<(&'a [u8], &'a [u8]), Infallible>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next()
+            .map(|(key, value)| Ok((key.as_ref(), value.as_ref())))
+    }
+}
+
+impl<'a> DoubleEndedIterator for BTreeRange<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next_back()
+            .map(|(key, value)| Ok((key.as_ref(), value.as_ref())))
+    }
+}
+
+impl<'a> RawStore for &'a BTreeMap<Vec<u8>, Vec<u8>> {
+    type Error = Infallible;
+    type Value = &'a [u8];
+
+    fn get(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(BTreeMap::get(self, key).map(Vec::as_slice))
+    }
+
+    fn contains_key(self, key: &[u8]) -> Result<bool, Self::Error> {
+        Ok(BTreeMap::contains_key(self, key))
+    }
+}
+
+impl<'a> RawScanStore for &'a BTreeMap<Vec<u8>, Vec<u8>> {
+    type Key = &'a [u8];
+    type Range = BTreeRange<'a>;
+
+    fn range(self, range: (Bound<&[u8]>, Bound<&[u8]>)) -> Self::Range {
+        BTreeRange {
+            iter: BTreeMap::range::<[u8], _>(self, range),
+        }
+    }
+}
+
+impl RawExclusiveStore for &mut BTreeMap<Vec<u8>, Vec<u8>> {
+    fn read(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Infallible> {
+        Ok(BTreeMap::get(self, key).map(|value| Cow::Borrowed(value.as_slice())))
+    }
+}
+
+// Splits the removed range off of the map rather than removing keys one at a time
+impl RawRemoveRangeStore for &mut BTreeMap<Vec<u8>, Vec<u8>> {
+    type Error = Infallible;
+
+    fn remove_range_with(
+        self,
+        (start, end): (Bound<&[u8]>, Bound<&[u8]>),
+        mut f: impl FnMut(&[u8], &[u8], &mut RecordBatch),
+    ) -> Result<usize, Self::Error> {
+        let mut removed = match start {
+            Bound::Included(start) | Bound::Excluded(start) => BTreeMap::split_off(self, start),
+            Bound::Unbounded => std::mem::take(self),
+        };
+
+        let mut rest = match end {
+            Bound::Included(end) => {
+                let mut rest = removed.split_off(end);
+                if let Some(value) = rest.remove(end) {
+                    removed.insert(end.into(), value);
+                }
+
+                rest
+            }
+            Bound::Excluded(end) => removed.split_off(end),
+            Bound::Unbounded => BTreeMap::new(),
+        };
+
+        if let Bound::Excluded(start) = start {
+            if let Some(value) = removed.remove(start) {
+                rest.insert(start.into(), value);
+            }
+        }
+
+        self.append(&mut rest);
+
+        let mut batch = RecordBatch::new();
+        for (key, value) in &removed {
+            f(key, value, &mut batch);
+        }
+
+        // Removals don't claim any index entries, so they can't violate a unique index
+        RawBatchStore::apply_batch(self, batch).map(|_| removed.len())
+    }
+}
+
+impl RawBatchStore for &mut BTreeMap<Vec<u8>, Vec<u8>> {
+    type Error = Infallible;
+
+    fn apply_batch(self, batch: RecordBatch) -> Result<Result<(), UniqueViolation>, Self::Error> {
+        let batch = match batch.prepare(|key| RawStore::get(&*self, key))? {
+            Ok(batch) => batch,
+            Err(err) => return Ok(Err(err)),
+        };
+
+        for (key, value) in batch {
+            match value {
+                Some(value) => BTreeMap::insert(self, key, value),
+                None => BTreeMap::remove(self, &key),
+            };
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+impl RawReplaceStore for &mut BTreeMap<Vec<u8>, Vec<u8>> {
+    type Error = Infallible;
+    type Value = Vec<u8>;
+
+    fn replace(self, key: &[u8], value: Vec<u8>) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(BTreeMap::insert(self, key.into(), value))
+    }
+
+    fn take(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(BTreeMap::remove(self, key))
+    }
+}
+
+impl<R: Record> Sequence<R> for &mut BTreeMap<Vec<u8>, Vec<u8>> {
+    type Error = Infallible;
+
+    fn next_id(&mut self) -> Result<Result<u64, SequenceError>, Self::Error> {
+        let next = sequence_key::<R>().and_then(|key| {
+            next_counter(BTreeMap::get(self, &key).map(Vec::as_slice)).map(|next| (key, next))
+        });
+        let (key, (id, counter)) = match next {
+            Ok(next) => next,
+            Err(err) => return Ok(Err(err)),
+        };
+        BTreeMap::insert(self, key, counter);
+
+        Ok(Ok(id))
+    }
+}
+
+pub type BTreeStoreIter<'a, R> = StoreIter<R, &'a BTreeMap<Vec<u8>, Vec<u8>>>;
+
+/// An in-memory transaction over a `BTreeMap` store, mirroring sled's `TransactionalTree`.
+///
+/// Writes are buffered until the transaction commits, and are visible to reads made within the transaction.
+/// Like `&TransactionalTree`, `&BTreeTransaction` implements every store trait except scanning.
+#[derive(Debug)]
+pub struct BTreeTransaction<'a> {
+    store: &'a BTreeMap<Vec<u8>, Vec<u8>>,
+    writes: RefCell<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+}
+
+impl<'a> BTreeTransaction<'a> {
+    /// Runs `f` in a transaction over `store`. The transaction's writes are applied if `f` succeeds, and discarded
+    /// if it fails.
+    pub fn run<A, E>(
+        store: &mut BTreeMap<Vec<u8>, Vec<u8>>,
+        f: impl FnOnce(&BTreeTransaction<'_>) -> Result<A, E>,
+    ) -> Result<A, E> {
+        let tx = BTreeTransaction {
+            store,
+            writes: RefCell::default(),
+        };
+
+        let value = f(&tx)?;
+        let writes = tx.writes.into_inner();
+
+        for (key, value) in writes {
+            match value {
+                Some(value) => store.insert(key, value),
+                None => store.remove(&key),
+            };
+        }
+
+        Ok(value)
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.writes.borrow().get(key) {
+            Some(value) => value.clone(),
+            None => self.store.get(key).cloned(),
+        }
+    }
+
+    fn write(&self, key: &[u8], value: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        let previous = self.get(key);
+        self.writes.borrow_mut().insert(key.into(), value);
+
+        previous
+    }
+}
+
+impl RawStore for &BTreeTransaction<'_> {
+    type Error = Infallible;
+    type Value = Vec<u8>;
+
+    fn get(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(BTreeTransaction::get(self, key))
+    }
+}
+
+impl RawExclusiveStore for &BTreeTransaction<'_> {
+    fn read(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Infallible> {
+        Ok(BTreeTransaction::get(self, key).map(Cow::Owned))
+    }
+}
+
+impl RawBatchStore for &BTreeTransaction<'_> {
+    type Error = Infallible;
+
+    fn apply_batch(self, batch: RecordBatch) -> Result<Result<(), UniqueViolation>, Self::Error> {
+        let batch = match batch.prepare(|key| RawStore::get(self, key))? {
+            Ok(batch) => batch,
+            Err(err) => return Ok(Err(err)),
+        };
+
+        for (key, value) in batch {
+            self.write(&key, value);
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+impl RawReplaceStore for &BTreeTransaction<'_> {
+    type Error = Infallible;
+    type Value = Vec<u8>;
+
+    fn replace(self, key: &[u8], value: Vec<u8>) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(self.write(key, Some(value)))
+    }
+
+    fn take(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(self.write(key, None))
+    }
+}
+
+impl<R: Record> Sequence<R> for &BTreeTransaction<'_> {
+    type Error = Infallible;
+
+    fn next_id(&mut self) -> Result<Result<u64, SequenceError>, Self::Error> {
+        let next = sequence_key::<R>().and_then(|key| {
+            next_counter(BTreeTransaction::get(self, &key).as_deref()).map(|next| (key, next))
+        });
+        let (key, (id, counter)) = match next {
+            Ok(next) => next,
+            Err(err) => return Ok(Err(err)),
+        };
+        self.write(&key, Some(counter));
+
+        Ok(Ok(id))
+    }
+}
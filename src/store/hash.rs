@@ -0,0 +1,57 @@
+use std::{borrow::Cow, collections::HashMap, convert::Infallible, hash::BuildHasher};
+
+use super::{
+    RawBatchStore, RawExclusiveStore, RawReplaceStore, RawStore, RecordBatch, UniqueViolation,
+};
+
+impl<'a, H: BuildHasher> RawStore for &'a HashMap<Vec<u8>, Vec<u8>, H> {
+    type Error = Infallible;
+    type Value = &'a [u8];
+
+    fn get(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(HashMap::get(self, key).map(Vec::as_slice))
+    }
+
+    fn contains_key(self, key: &[u8]) -> Result<bool, Self::Error> {
+        Ok(HashMap::contains_key(self, key))
+    }
+}
+
+impl<H: BuildHasher> RawExclusiveStore for &mut HashMap<Vec<u8>, Vec<u8>, H> {
+    fn read(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Infallible> {
+        Ok(HashMap::get(self, key).map(|value| Cow::Borrowed(value.as_slice())))
+    }
+}
+
+impl<H: BuildHasher> RawBatchStore for &mut HashMap<Vec<u8>, Vec<u8>, H> {
+    type Error = Infallible;
+
+    fn apply_batch(self, batch: RecordBatch) -> Result<Result<(), UniqueViolation>, Self::Error> {
+        let batch = match batch.prepare(|key| RawStore::get(&*self, key))? {
+            Ok(batch) => batch,
+            Err(err) => return Ok(Err(err)),
+        };
+
+        for (key, value) in batch {
+            match value {
+                Some(value) => HashMap::insert(self, key, value),
+                None => HashMap::remove(self, &key),
+            };
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+impl<H: BuildHasher> RawReplaceStore for &mut HashMap<Vec<u8>, Vec<u8>, H> {
+    type Error = Infallible;
+    type Value = Vec<u8>;
+
+    fn replace(self, key: &[u8], value: Vec<u8>) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(HashMap::insert(self, key.into(), value))
+    }
+
+    fn take(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(HashMap::remove(self, key))
+    }
+}
@@ -0,0 +1,372 @@
+use std::{fmt::Display, marker::PhantomData};
+
+use super::{
+    as_slices, prefix_range, strip_table, table_key, RawScanStore, ReadStoreError, RecordBatch,
+    RecordReadError,
+};
+use crate::{DecodeKey, EncodeKey, PrefixKey, Record};
+
+/// A secondary index over records of type `R`, which looks records up by a key derived from them.
+///
+/// Indexes are declared in [`Record::indexes`], after which every write keeps their entries in sync. Each entry is
+/// stored under the index's table followed by the index key and the record's key (so index keys needn't be unique),
+/// and holds the record's key.
+pub trait Index<R: Record> {
+    type Key: EncodeKey;
+
+    /// Prepended to every entry of this index. Like [`Record::TABLE`], it shouldn't be a prefix of any other table
+    /// in the same store.
+    const TABLE: &'static [u8];
+
+    /// Whether each index key may only belong to a single record. Writing a record whose index key already belongs to
+    /// another record fails with a [`UniqueViolation`], and writes nothing.
+    ///
+    /// The check is atomic for every store that can read. Writes to a [`RecordBatch`] are checked when it's applied,
+    /// and writes to a sled `Batch` can't be checked, so fail with
+    /// [`WriteStoreError::IndexUnsupported`](crate::WriteStoreError::IndexUnsupported). Entries of
+    /// unique indexes are stored under just the index key.
+    const UNIQUE: bool = false;
+
+    fn key(record: &R) -> Self::Key;
+}
+
+/// An [`Index`] of records of type `R`, as declared in [`Record::indexes`]
+pub struct IndexDef<R> {
+    table: &'static [u8],
+    unique: bool,
+    encode: fn(&R) -> Vec<u8>,
+    decode: EntryFn,
+}
+
+/// Returns the raw key of a unique index entry for the record stored under an encoded key and value, if it decodes
+type EntryFn = fn(&[u8], &[u8]) -> Option<Vec<u8>>;
+
+impl<R: Record> IndexDef<R> {
+    pub fn of<I: Index<R>>() -> Self {
+        Self {
+            table: I::TABLE,
+            unique: I::UNIQUE,
+            encode: |record| I::key(record).encode().as_ref().to_owned(),
+            decode: |primary, value| {
+                let (key, _) = <R::Key as DecodeKey>::try_decode(primary).ok()?;
+                let record = R::try_decode(key, value).ok()?;
+
+                Some(IndexDef::of::<I>().entry(&record, primary))
+            },
+        }
+    }
+
+    /// Returns the raw key of the entry for `record`, which is stored under the encoded key `primary`
+    fn entry(&self, record: &R, primary: &[u8]) -> Vec<u8> {
+        let key = (self.encode)(record);
+
+        let mut bytes = Vec::with_capacity(self.table.len() + key.len() + primary.len());
+        bytes.extend_from_slice(self.table);
+        bytes.extend_from_slice(&key);
+        if !self.unique {
+            bytes.extend_from_slice(primary);
+        }
+
+        bytes
+    }
+}
+
+/// Whether records of type `R` have indexes but no [`Record::TABLE`], so their index entries would be stored among
+/// them and visited by every scan. Writing such a record fails instead.
+pub(crate) fn missing_table<R: Record>() -> bool {
+    R::TABLE.is_empty() && !R::indexes().is_empty()
+}
+
+/// An index entry belonging to a record
+#[derive(Clone)]
+pub(crate) struct Entry {
+    key: Vec<u8>,
+    index: &'static [u8],
+    /// Set for entries of unique indexes, which have to be claimed rather than just written
+    unique: Option<EntryFn>,
+}
+
+/// Returns every index entry for `record`, where `primary` is its encoded key
+pub(crate) fn index_entries<R: Record>(record: &R, primary: &[u8]) -> Vec<Entry> {
+    R::indexes()
+        .iter()
+        .map(|index| Entry {
+            key: index.entry(record, primary),
+            index: index.table,
+            unique: if index.unique {
+                Some(index.decode)
+            } else {
+                None
+            },
+        })
+        .collect()
+}
+
+/// Returns every index entry for the record stored as `value`.
+/// A value that can't be decoded has no entries, since any it left behind are skipped by index scans.
+pub(crate) fn value_entries<R: Record>(
+    key: &R::Key,
+    primary: &[u8],
+    value: Option<&[u8]>,
+) -> Vec<Entry> {
+    match value.map(|value| R::try_decode(key.clone(), value)) {
+        Some(Ok(record)) => index_entries(&record, primary),
+        _ => Vec::new(),
+    }
+}
+
+/// Adds the writes that replace the index entries `old` with `new` to `batch`, where `primary` is the encoded key
+/// of the record of type `R` they belong to.
+///
+/// A [`RecordBatch`] that's being written to can't see the record being replaced, so the writes are deferred until
+/// it's applied instead.
+pub(crate) fn index_writes<R: Record>(
+    primary: &[u8],
+    old: Vec<Entry>,
+    new: Vec<Entry>,
+    batch: &mut RecordBatch,
+) {
+    if batch.is_deferring() {
+        batch.defer(Pending {
+            key: table_key::<R>(primary),
+            resolve: resolve::<R>,
+        });
+        return;
+    }
+
+    let contains = |entries: &[Entry], entry: &Entry| entries.iter().any(|e| e.key == entry.key);
+
+    for entry in old.iter().filter(|entry| !contains(&new, entry)) {
+        batch.push(entry.key.clone(), None);
+    }
+
+    for entry in new.iter().filter(|entry| !contains(&old, entry)) {
+        match entry.unique {
+            Some(decode) => batch.claim(Claim {
+                key: entry.key.clone(),
+                owner: primary.to_owned(),
+                index: entry.index,
+                table: R::TABLE,
+                decode,
+            }),
+            None => batch.push(entry.key.clone(), Some(primary.to_owned())),
+        }
+    }
+}
+
+/// Adds the writes that replace the index entries of the record of type `R` stored as `old` under the raw key `key`
+/// with those of the record stored as `new` to `batch`
+fn resolve<R: Record>(key: &[u8], old: Option<&[u8]>, new: Option<&[u8]>, batch: &mut RecordBatch) {
+    let primary = strip_table::<R>(key);
+
+    // Like a value that can't be decoded, a key that can't be decoded has no entries
+    if let Ok((key, _)) = <R::Key as DecodeKey>::try_decode(primary) {
+        index_writes::<R>(
+            primary,
+            value_entries::<R>(&key, primary, old),
+            value_entries::<R>(&key, primary, new),
+            batch,
+        );
+    }
+}
+
+/// A record written to a [`RecordBatch`], whose index writes are deferred until the batch is applied and the record
+/// it replaces can be read
+#[derive(Debug, Clone)]
+pub(crate) struct Pending {
+    /// The raw key the record is stored under
+    pub(crate) key: Vec<u8>,
+    pub(crate) resolve: ResolveFn,
+}
+
+/// Adds the index writes of a record to a batch, given its raw key and the values it's replacing and replaced with
+type ResolveFn = fn(&[u8], Option<&[u8]>, Option<&[u8]>, &mut RecordBatch);
+
+// The resolve function is determined by the record type, so it's left out
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Pending {}
+
+/// A write of a unique index entry, which fails if the entry already belongs to another record
+#[derive(Debug, Clone)]
+pub(crate) struct Claim {
+    pub(crate) key: Vec<u8>,
+    /// The encoded key of the record claiming the entry
+    pub(crate) owner: Vec<u8>,
+    pub(crate) index: &'static [u8],
+    /// The table of the record claiming the entry
+    pub(crate) table: &'static [u8],
+    pub(crate) decode: EntryFn,
+}
+
+impl Claim {
+    /// Checks whether the record stored under the encoded key `owner`, read with `get`, has this entry's index key.
+    /// An entry left behind by a record that no longer has this index key doesn't hold it.
+    pub(crate) fn is_held_by<E>(
+        &self,
+        owner: &[u8],
+        mut get: impl FnMut(&[u8]) -> Result<Option<Vec<u8>>, E>,
+    ) -> Result<bool, E> {
+        let mut key = self.table.to_owned();
+        key.extend_from_slice(owner);
+
+        Ok(match get(&key)? {
+            Some(value) => (self.decode)(owner, &value).as_ref() == Some(&self.key),
+            None => false,
+        })
+    }
+}
+
+// The decode function is determined by the index, so it's left out
+impl PartialEq for Claim {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+            && self.owner == other.owner
+            && self.index == other.index
+            && self.table == other.table
+    }
+}
+
+impl Eq for Claim {}
+
+/// Returned when writing a record whose key in a unique [`Index`] already belongs to another record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UniqueViolation {
+    /// The table of the violated index
+    pub index: &'static [u8],
+}
+
+impl Display for UniqueViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Another record already has this key in unique index {:?}",
+            String::from_utf8_lossy(self.index)
+        )
+    }
+}
+
+impl std::error::Error for UniqueViolation {}
+
+/// Why the index entries of a record couldn't be written along with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexWriteError {
+    UniqueViolation(UniqueViolation),
+    /// The store can't write index entries (or for unique indexes, can't check them), so nothing was written
+    Unsupported,
+}
+
+impl Display for IndexWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndexWriteError::UniqueViolation(err) => Display::fmt(err, f),
+            IndexWriteError::Unsupported => {
+                write!(f, "This store can't keep the record's indexes in sync")
+            }
+        }
+    }
+}
+
+impl std::error::Error for IndexWriteError {}
+
+impl From<UniqueViolation> for IndexWriteError {
+    fn from(err: UniqueViolation) -> Self {
+        IndexWriteError::UniqueViolation(err)
+    }
+}
+
+/// A store that can look up records through their secondary indexes
+pub trait IndexStore<R: Record>: RawScanStore + Copy {
+    /// Iterates over the records whose key in the index `I` starts with `prefix`, in index key order
+    fn scan_index<I: Index<R>>(self, prefix: &impl PrefixKey<I::Key>) -> IndexIter<R, I, Self>;
+}
+
+impl<R: Record, S: RawScanStore + Copy> IndexStore<R> for S {
+    fn scan_index<I: Index<R>>(self, prefix: &impl PrefixKey<I::Key>) -> IndexIter<R, I, Self> {
+        let mut start = I::TABLE.to_owned();
+        start.extend_from_slice(prefix.encode().as_ref());
+
+        IndexIter {
+            store: self,
+            iter: self.range(as_slices(&prefix_range(start))),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Typed iterator over the records found through an [`Index`].
+///
+/// Every entry is checked against the record it points to, and skipped if the record no longer exists or has
+/// since moved to a different index key (which can happen after writing to a store that can't see the records it
+/// replaces, like a sled `Batch`).
+pub struct IndexIter<R: Record, I: Index<R>, S: RawScanStore> {
+    store: S,
+    iter: S::Range,
+    _phantom: PhantomData<(R, I)>,
+}
+
+impl<R: Record, I: Index<R>, S: RawScanStore + Copy> IndexIter<R, I, S> {
+    fn resolve(
+        &self,
+        entry: Result<(S::Key, S::Value), S::Error>,
+    ) -> Option<Result<R, RecordReadError<R, S::Error>>> {
+        let (entry, primary) = match entry {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(ReadStoreError::StoreError(err))),
+        };
+        let primary = primary.as_ref();
+
+        let key = match <R::Key as DecodeKey>::try_decode(primary) {
+            Ok((key, _)) => key,
+            Err(err) => return Some(Err(ReadStoreError::KeyDecodeErr(err))),
+        };
+
+        let value = match self.store.get(&table_key::<R>(primary)) {
+            Ok(Some(value)) => value,
+            Ok(None) => return None,
+            Err(err) => return Some(Err(ReadStoreError::StoreError(err))),
+        };
+
+        let record = match R::try_decode(key, value.as_ref()) {
+            Ok(record) => record,
+            Err(err) => return Some(Err(ReadStoreError::ValueDecodeError(err))),
+        };
+
+        let current = IndexDef::of::<I>().entry(&record, primary);
+        if current.as_slice() == entry.as_ref() {
+            Some(Ok(record))
+        } else {
+            None
+        }
+    }
+}
+
+impl<R: Record, I: Index<R>, S: RawScanStore + Copy> Iterator for IndexIter<R, I, S> {
+    type Item = Result<R, RecordReadError<R, S::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entry) = self.iter.next() {
+            if let Some(record) = self.resolve(entry) {
+                return Some(record);
+            }
+        }
+
+        None
+    }
+}
+
+impl<R: Record, I: Index<R>, S: RawScanStore + Copy> DoubleEndedIterator for IndexIter<R, I, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(entry) = self.iter.next_back() {
+            if let Some(record) = self.resolve(entry) {
+                return Some(record);
+            }
+        }
+
+        None
+    }
+}
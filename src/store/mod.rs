@@ -0,0 +1,1066 @@
+use std::{
+    convert::TryInto,
+    fmt::Display,
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{DecodeKey, EncodeKey, PrefixKey, Record};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadStoreError<S: std::error::Error, K: std::error::Error, V: std::error::Error> {
+    StoreError(S),
+    KeyDecodeErr(K),
+    ValueDecodeError(V),
+    /// A page was requested with a limit of 0, which would never make progress
+    ZeroLimit,
+}
+
+impl<S: std::error::Error, K: std::error::Error, V: std::error::Error> Display
+    for ReadStoreError<S, K, V>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StoreError(err) => Display::fmt(err, f),
+            Self::KeyDecodeErr(err) => Display::fmt(err, f),
+            Self::ValueDecodeError(err) => Display::fmt(err, f),
+            Self::ZeroLimit => write!(f, "A page can't have a limit of 0"),
+        }
+    }
+}
+
+impl<S: std::error::Error, K: std::error::Error, V: std::error::Error> std::error::Error
+    for ReadStoreError<S, K, V>
+{
+}
+
+/// The [`ReadStoreError`] produced when reading records of type `R` from a store with error type `E`
+pub type RecordReadError<R, E> =
+    ReadStoreError<E, <<R as Record>::Key as DecodeKey>::Error, <R as Record>::DecodeError>;
+
+/// A store that can look up individual records by key
+pub trait PointReadStore<R: Record> {
+    type Error: std::error::Error;
+
+    fn fetch(self, key: &R::Key) -> Result<Option<R>, RecordReadError<R, Self::Error>>;
+
+    /// Checks whether a record exists, without decoding it
+    fn exists(self, key: &R::Key) -> Result<bool, Self::Error>;
+}
+
+/// A store that can scan through its records in key order.
+///
+/// Kept separate from [`PointReadStore`], since not every store supports ordered iteration
+/// (e.g. sled transactions or hash maps).
+pub trait ScanStore<R: Record>: PointReadStore<R> {
+    type Iter: DoubleEndedIterator<Item = Result<R, RecordReadError<R, Self::Error>>>;
+    /// Iterator over just the keys of records, which never decodes values
+    /// (and so never produces [`ReadStoreError::ValueDecodeError`])
+    type KeyIter: DoubleEndedIterator<Item = Result<R::Key, RecordReadError<R, Self::Error>>>;
+
+    fn scan(self) -> Self::Iter;
+    fn scan_range<P: PrefixKey<R::Key>>(self, range: impl RangeBounds<P>) -> Self::Iter;
+    fn scan_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Self::Iter;
+
+    fn scan_keys(self) -> Self::KeyIter;
+    fn scan_keys_range<P: PrefixKey<R::Key>>(self, range: impl RangeBounds<P>) -> Self::KeyIter;
+    fn scan_keys_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Self::KeyIter;
+
+    /// Counts the records in `range`, without decoding them
+    fn count_range<P: PrefixKey<R::Key>>(
+        self,
+        range: impl RangeBounds<P>,
+    ) -> Result<usize, Self::Error>;
+    /// Counts the records starting with `prefix`, without decoding them
+    fn count_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Result<usize, Self::Error>;
+
+    /// Estimates the size in bytes of the records in `range`, without decoding them
+    fn size_range<P: PrefixKey<R::Key>>(
+        self,
+        range: impl RangeBounds<P>,
+    ) -> Result<u64, Self::Error>;
+    /// Estimates the size in bytes of the records starting with `prefix`, without decoding them
+    fn size_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Result<u64, Self::Error>;
+
+    /// Returns up to `limit` records in `range`, starting after `after` (if given)
+    ///
+    /// Fails with [`ReadStoreError::ZeroLimit`] if `limit` is 0.
+    fn scan_range_page<P: PrefixKey<R::Key>>(
+        self,
+        range: impl RangeBounds<P>,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<R>, RecordReadError<R, Self::Error>>;
+
+    /// Returns up to `limit` records starting with `prefix`, starting after `after` (if given)
+    ///
+    /// Fails with [`ReadStoreError::ZeroLimit`] if `limit` is 0.
+    fn scan_prefix_page<P: PrefixKey<R::Key>>(
+        self,
+        prefix: &P,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<R>, RecordReadError<R, Self::Error>>;
+}
+
+/// A store that supports both point reads and ordered scans
+pub trait ReadStore<R: Record>: PointReadStore<R> + ScanStore<R> {}
+
+impl<R: Record, S: PointReadStore<R> + ScanStore<R>> ReadStore<R> for S {}
+
+/// Typed iterator over the records in a [`RawScanStore`]
+pub struct StoreIter<R: Record, S: RawScanStore> {
+    iter: S::Range,
+    _phantom: PhantomData<R>,
+}
+
+/// Typed iterator over the keys in a [`RawScanStore`]
+pub struct KeyIter<R: Record, S: RawScanStore> {
+    iter: S::Range,
+    _phantom: PhantomData<R>,
+}
+
+impl<R: Record, S: RawScanStore> KeyIter<R, S> {
+    fn decode(entry: Result<(S::Key, S::Value), S::Error>) -> <Self as Iterator>::Item {
+        let (key, _) = entry.map_err(ReadStoreError::StoreError)?;
+
+        match <R::Key as DecodeKey>::try_decode(strip_table::<R>(key.as_ref())) {
+            Ok((key, _)) => Ok(key),
+            Err(err) => Err(ReadStoreError::KeyDecodeErr(err)),
+        }
+    }
+}
+
+impl<R: Record, S: RawScanStore> Iterator for KeyIter<R, S> {
+    type Item = Result<R::Key, RecordReadError<R, S::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(Self::decode)
+    }
+}
+
+impl<R: Record, S: RawScanStore> DoubleEndedIterator for KeyIter<R, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(Self::decode)
+    }
+}
+
+/// Decodes a raw key and value into a record
+fn decode_record<R: Record, E: std::error::Error>(
+    key: &[u8],
+    value: &[u8],
+) -> Result<R, RecordReadError<R, E>> {
+    let key = match <R::Key as DecodeKey>::try_decode(strip_table::<R>(key)) {
+        Ok((key, _)) => key,
+        Err(err) => return Err(ReadStoreError::KeyDecodeErr(err)),
+    };
+
+    R::try_decode(key, value).map_err(ReadStoreError::ValueDecodeError)
+}
+
+/// Decodes a raw entry from a [`RawScanStore`] into a record
+fn decode_entry<R: Record, K: AsRef<[u8]>, V: AsRef<[u8]>, E: std::error::Error>(
+    entry: Result<(K, V), E>,
+) -> Result<R, RecordReadError<R, E>> {
+    let (key, value) = entry.map_err(ReadStoreError::StoreError)?;
+
+    decode_record(key.as_ref(), value.as_ref())
+}
+
+impl<R: Record, S: RawScanStore> Iterator for StoreIter<R, S> {
+    type Item = Result<R, RecordReadError<R, S::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(decode_entry)
+    }
+}
+
+impl<R: Record, S: RawScanStore> DoubleEndedIterator for StoreIter<R, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(decode_entry)
+    }
+}
+
+/// Returns the smallest key that is greater than every key starting with `prefix`,
+/// or `None` if there is no such key (i.e. the prefix is empty or all `0xFF`)
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_owned();
+
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+
+    None
+}
+
+/// Prepends the table of `R` to an encoded key
+fn table_key<R: Record>(key: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(R::TABLE.len() + key.len());
+    bytes.extend_from_slice(R::TABLE);
+    bytes.extend_from_slice(key);
+
+    bytes
+}
+
+/// Encodes a key into the raw key it's stored under, including the table of `R`
+fn encode_key<R: Record>(key: &R::Key) -> Vec<u8> {
+    table_key::<R>(key.encode().as_ref())
+}
+
+/// Strips the table of `R` from a raw key, leaving just the encoded key
+fn strip_table<R: Record>(key: &[u8]) -> &[u8] {
+    key.get(R::TABLE.len()..).unwrap_or_default()
+}
+
+/// Returns the range of raw keys starting with `start`
+fn prefix_range(start: Vec<u8>) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    match prefix_successor(&start) {
+        Some(end) => (Bound::Included(start), Bound::Excluded(end)),
+        None => (Bound::Included(start), Bound::Unbounded),
+    }
+}
+
+/// Encodes a range of prefix keys into a range of raw keys within the table of `R`.
+/// An included end bound includes every key starting with that prefix.
+fn encode_range<R: Record, P: PrefixKey<R::Key>>(
+    range: impl RangeBounds<P>,
+) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    let start = match range.start_bound() {
+        Bound::Excluded(start) => Bound::Excluded(table_key::<R>(start.encode().as_ref())),
+        Bound::Included(start) => Bound::Included(table_key::<R>(start.encode().as_ref())),
+        Bound::Unbounded => Bound::Included(R::TABLE.to_owned()),
+    };
+
+    let end = match range.end_bound() {
+        Bound::Excluded(end) => Bound::Excluded(table_key::<R>(end.encode().as_ref())),
+        Bound::Included(end) => prefix_range(table_key::<R>(end.encode().as_ref())).1,
+        Bound::Unbounded => prefix_range(R::TABLE.to_owned()).1,
+    };
+
+    (start, end)
+}
+
+/// Encodes a prefix key into the range of raw keys starting with it, within the table of `R`
+fn encode_prefix<R: Record, P: PrefixKey<R::Key>>(prefix: &P) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    prefix_range(table_key::<R>(prefix.encode().as_ref()))
+}
+
+/// Returns the range of raw keys covering the whole table of `R`
+fn encode_table<R: Record>() -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    prefix_range(R::TABLE.to_owned())
+}
+
+fn as_slice(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Excluded(bytes) => Bound::Excluded(bytes.as_slice()),
+        Bound::Included(bytes) => Bound::Included(bytes.as_slice()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+fn as_slices(range: &(Bound<Vec<u8>>, Bound<Vec<u8>>)) -> (Bound<&[u8]>, Bound<&[u8]>) {
+    (as_slice(&range.0), as_slice(&range.1))
+}
+
+impl<R: Record, S: RawStore> PointReadStore<R> for S {
+    type Error = S::Error;
+
+    fn fetch(self, key: &R::Key) -> Result<Option<R>, RecordReadError<R, Self::Error>> {
+        let value = match self.get(encode_key::<R>(key).as_slice()) {
+            Ok(Some(value)) => value,
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(ReadStoreError::StoreError(err)),
+        };
+
+        match R::try_decode(key.clone(), value.as_ref()) {
+            Ok(record) => Ok(Some(record)),
+            Err(err) => Err(ReadStoreError::ValueDecodeError(err)),
+        }
+    }
+
+    fn exists(self, key: &R::Key) -> Result<bool, Self::Error> {
+        self.contains_key(encode_key::<R>(key).as_slice())
+    }
+}
+
+impl<R: Record, S: RawScanStore> ScanStore<R> for S {
+    type Iter = StoreIter<R, S>;
+    type KeyIter = KeyIter<R, S>;
+
+    fn scan(self) -> Self::Iter {
+        StoreIter {
+            iter: self.range(as_slices(&encode_table::<R>())),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn scan_range<P: PrefixKey<R::Key>>(self, range: impl RangeBounds<P>) -> Self::Iter {
+        StoreIter {
+            iter: self.range(as_slices(&encode_range::<R, _>(range))),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn scan_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Self::Iter {
+        StoreIter {
+            iter: self.range(as_slices(&encode_prefix::<R, _>(prefix))),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn scan_keys(self) -> Self::KeyIter {
+        KeyIter {
+            iter: self.range(as_slices(&encode_table::<R>())),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn scan_keys_range<P: PrefixKey<R::Key>>(self, range: impl RangeBounds<P>) -> Self::KeyIter {
+        KeyIter {
+            iter: self.range(as_slices(&encode_range::<R, _>(range))),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn scan_keys_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Self::KeyIter {
+        KeyIter {
+            iter: self.range(as_slices(&encode_prefix::<R, _>(prefix))),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn count_range<P: PrefixKey<R::Key>>(
+        self,
+        range: impl RangeBounds<P>,
+    ) -> Result<usize, Self::Error> {
+        self.count(as_slices(&encode_range::<R, _>(range)))
+    }
+
+    fn count_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Result<usize, Self::Error> {
+        self.count(as_slices(&encode_prefix::<R, _>(prefix)))
+    }
+
+    fn size_range<P: PrefixKey<R::Key>>(
+        self,
+        range: impl RangeBounds<P>,
+    ) -> Result<u64, Self::Error> {
+        self.size(as_slices(&encode_range::<R, _>(range)))
+    }
+
+    fn size_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Result<u64, Self::Error> {
+        self.size(as_slices(&encode_prefix::<R, _>(prefix)))
+    }
+
+    fn scan_range_page<P: PrefixKey<R::Key>>(
+        self,
+        range: impl RangeBounds<P>,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<R>, RecordReadError<R, Self::Error>> {
+        scan_page(self, encode_range::<R, _>(range), after, limit)
+    }
+
+    fn scan_prefix_page<P: PrefixKey<R::Key>>(
+        self,
+        prefix: &P,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<R>, RecordReadError<R, Self::Error>> {
+        scan_page(self, encode_prefix::<R, _>(prefix), after, limit)
+    }
+}
+
+fn scan_page<R: Record, S: RawScanStore>(
+    store: S,
+    (start, end): (Bound<Vec<u8>>, Bound<Vec<u8>>),
+    after: Option<&Cursor>,
+    limit: usize,
+) -> Result<Page<R>, RecordReadError<R, S::Error>> {
+    if limit == 0 {
+        return Err(ReadStoreError::ZeroLimit);
+    }
+
+    let start = match page::resume(start, &end, after) {
+        Some(start) => start,
+        None => {
+            return Ok(Page {
+                records: Vec::new(),
+                next: None,
+            })
+        }
+    };
+    let mut iter = store.range((as_slice(&start), as_slice(&end)));
+
+    let mut records = Vec::with_capacity(limit);
+    let mut last = None;
+
+    for entry in iter.by_ref().take(limit) {
+        let (key, value) = entry.map_err(ReadStoreError::StoreError)?;
+
+        records.push(decode_record(key.as_ref(), value.as_ref())?);
+        last = Some(key);
+    }
+
+    // Only hand out a cursor if there's at least one more entry, to save callers from fetching an empty page
+    let next = match (last, iter.next()) {
+        (Some(last), Some(_)) => Some(Cursor::from_bytes(last.as_ref().to_owned())),
+        _ => None,
+    };
+
+    Ok(Page { records, next })
+}
+
+pub trait WriteStore<R: Record> {
+    type Error: std::error::Error;
+
+    fn persist(self, record: &R) -> Result<(), WriteStoreError<Self::Error, R::EncodeError>>;
+    fn remove(self, key: &R::Key) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteStoreError<S: std::error::Error, V: std::error::Error> {
+    StoreError(S),
+    EncodeError(V),
+    UniqueViolation(UniqueViolation),
+    /// The record has a different key from the one it's being written under
+    KeyMismatch,
+    /// The record has indexes the store can't keep in sync, so nothing was written
+    IndexUnsupported,
+    /// The record has indexes but no [`Record::TABLE`] to keep them apart from its records, so nothing was written
+    NoTable,
+}
+
+impl<S: std::error::Error, V: std::error::Error> Display for WriteStoreError<S, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteStoreError::StoreError(err) => Display::fmt(err, f),
+            WriteStoreError::EncodeError(err) => Display::fmt(err, f),
+            WriteStoreError::UniqueViolation(err) => Display::fmt(err, f),
+            WriteStoreError::KeyMismatch => {
+                write!(
+                    f,
+                    "The record's key doesn't match the key it's written under"
+                )
+            }
+            WriteStoreError::IndexUnsupported => {
+                write!(f, "This store can't keep the record's indexes in sync")
+            }
+            WriteStoreError::NoTable => write!(f, "Records with indexes need a table"),
+        }
+    }
+}
+
+impl<S: std::error::Error, V: std::error::Error> std::error::Error for WriteStoreError<S, V> {}
+
+impl<S: std::error::Error, V: std::error::Error> From<UniqueViolation> for WriteStoreError<S, V> {
+    fn from(err: UniqueViolation) -> Self {
+        WriteStoreError::UniqueViolation(err)
+    }
+}
+
+impl<S: std::error::Error, V: std::error::Error> From<IndexWriteError> for WriteStoreError<S, V> {
+    fn from(err: IndexWriteError) -> Self {
+        match err {
+            IndexWriteError::UniqueViolation(err) => WriteStoreError::UniqueViolation(err),
+            IndexWriteError::Unsupported => WriteStoreError::IndexUnsupported,
+        }
+    }
+}
+
+impl<S, K, V, W> From<UniqueViolation> for ReadStoreError<WriteStoreError<S, W>, K, V>
+where
+    S: std::error::Error,
+    K: std::error::Error,
+    V: std::error::Error,
+    W: std::error::Error,
+{
+    fn from(err: UniqueViolation) -> Self {
+        ReadStoreError::StoreError(err.into())
+    }
+}
+
+/// The error produced when updating records of type `R` in a store with error type `E`.
+///
+/// Decoding the current record fails with a [`ReadStoreError`], while encoding or writing the updated record
+/// fails with a [`WriteStoreError`] wrapped in [`ReadStoreError::StoreError`].
+pub type RecordUpdateError<R, E> =
+    RecordReadError<R, WriteStoreError<E, <R as Record>::EncodeError>>;
+
+/// A store that can write records while returning the records they replaced
+pub trait ReplaceStore<R: Record> {
+    type Error: std::error::Error;
+
+    /// Persists a record, returning the record previously stored under the same key
+    fn replace(self, record: &R) -> Result<Option<R>, RecordUpdateError<R, Self::Error>>;
+    /// Removes a record, returning it if it existed
+    fn take(self, key: &R::Key) -> Result<Option<R>, RecordReadError<R, Self::Error>>;
+}
+
+// Records with indexes go through `RawUpdateStore`, which can also see and rewrite their old index entries
+impl<R: Record, S> ReplaceStore<R> for S
+where
+    S: RawReplaceStore + RawUpdateStore<Error = <S as RawReplaceStore>::Error>,
+{
+    type Error = <S as RawReplaceStore>::Error;
+
+    fn replace(self, record: &R) -> Result<Option<R>, RecordUpdateError<R, Self::Error>> {
+        let (key, value) = match record.try_encode() {
+            Ok(data) => data,
+            Err(err) => {
+                return Err(ReadStoreError::StoreError(WriteStoreError::EncodeError(
+                    err,
+                )))
+            }
+        };
+
+        let previous = if R::indexes().is_empty() {
+            RawReplaceStore::replace(self, encode_key::<R>(&key).as_slice(), value)
+                .map(|value| value.map(|value| value.as_ref().to_owned()))
+        } else if R::TABLE.is_empty() {
+            return Err(ReadStoreError::StoreError(WriteStoreError::NoTable));
+        } else {
+            let primary = key.encode();
+            let entries = index_entries(record, primary.as_ref());
+
+            match update_indexed::<R, _>(self, &key, Some(value), entries) {
+                Ok(Ok(previous)) => Ok(previous),
+                Ok(Err(err)) => return Err(err.into()),
+                Err(err) => Err(err),
+            }
+        };
+
+        match previous {
+            Ok(Some(value)) => R::try_decode(key, &value)
+                .map(Some)
+                .map_err(ReadStoreError::ValueDecodeError),
+            Ok(None) => Ok(None),
+            Err(err) => Err(ReadStoreError::StoreError(WriteStoreError::StoreError(err))),
+        }
+    }
+
+    fn take(self, key: &R::Key) -> Result<Option<R>, RecordReadError<R, Self::Error>> {
+        let previous = if R::indexes().is_empty() {
+            RawReplaceStore::take(self, encode_key::<R>(key).as_slice())
+                .map(|value| value.map(|value| value.as_ref().to_owned()))
+        } else {
+            // Removing a record only removes index entries, so it can't violate a unique index
+            update_indexed::<R, _>(self, key, None, Vec::new())
+                .map(|result| result.unwrap_or_default())
+        };
+
+        match previous {
+            Ok(Some(value)) => R::try_decode(key.clone(), &value)
+                .map(Some)
+                .map_err(ReadStoreError::ValueDecodeError),
+            Ok(None) => Ok(None),
+            Err(err) => Err(ReadStoreError::StoreError(err)),
+        }
+    }
+}
+
+/// Writes `value` under `key` (removing it on `None`) along with the index entries `entries`, removing the entries
+/// of the record it replaces, and returns the raw value it replaced
+fn update_indexed<R: Record, S: RawUpdateStore>(
+    store: S,
+    key: &R::Key,
+    value: Option<Vec<u8>>,
+    entries: Vec<Entry>,
+) -> Result<Result<Option<Vec<u8>>, UniqueViolation>, S::Error> {
+    let primary = key.encode();
+    let primary = primary.as_ref();
+    let mut previous = None;
+
+    let result = RawUpdateStore::update(store, &table_key::<R>(primary), |current, batch| {
+        previous = current.map(<[u8]>::to_vec);
+        index_writes::<R>(
+            primary,
+            value_entries::<R>(key, primary, current),
+            entries.clone(),
+            batch,
+        );
+
+        Ok(value.clone())
+    });
+
+    result.map(|result| result.map(|()| previous))
+}
+
+/// A store that can atomically read, modify, and write back a record
+pub trait UpdateStore<R: Record> {
+    type Error: std::error::Error;
+
+    /// Replaces the record stored under `key` (removing it on `None`) with the result of `f`, which is passed the
+    /// current record, and returns the new record. `f` may be called more than once if the record is modified
+    /// concurrently, and the record it returns must have the same key, or nothing is written and the update fails
+    /// with [`WriteStoreError::KeyMismatch`].
+    fn update<F: FnMut(Option<R>) -> Option<R>>(
+        self,
+        key: &R::Key,
+        f: F,
+    ) -> Result<Option<R>, RecordUpdateError<R, Self::Error>>;
+}
+
+impl<R: Record, S: RawUpdateStore> UpdateStore<R> for S {
+    type Error = S::Error;
+
+    fn update<F: FnMut(Option<R>) -> Option<R>>(
+        self,
+        key: &R::Key,
+        mut f: F,
+    ) -> Result<Option<R>, RecordUpdateError<R, Self::Error>> {
+        if missing_table::<R>() {
+            return Err(ReadStoreError::StoreError(WriteStoreError::NoTable));
+        }
+
+        let primary = key.encode();
+        let primary = primary.as_ref();
+        let mut updated = None;
+
+        let result = RawUpdateStore::update(self, &table_key::<R>(primary), |current, batch| {
+            let current = match current {
+                Some(value) => Some(
+                    R::try_decode(key.clone(), value).map_err(ReadStoreError::ValueDecodeError)?,
+                ),
+                None => None,
+            };
+            let old = current
+                .as_ref()
+                .map_or_else(Vec::new, |record| index_entries(record, primary));
+
+            updated = f(current);
+
+            match &updated {
+                Some(record) => match record.try_encode() {
+                    Ok((new_key, _)) if new_key.encode().as_ref() != primary => {
+                        Err(ReadStoreError::StoreError(WriteStoreError::KeyMismatch))
+                    }
+                    Ok((_, value)) => {
+                        index_writes::<R>(primary, old, index_entries(record, primary), batch);
+                        Ok(Some(value))
+                    }
+                    Err(err) => Err(ReadStoreError::StoreError(WriteStoreError::EncodeError(
+                        err,
+                    ))),
+                },
+                None => {
+                    index_writes::<R>(primary, old, Vec::new(), batch);
+                    Ok(None)
+                }
+            }
+        });
+
+        match result {
+            Ok(Ok(())) => Ok(updated),
+            Ok(Err(err)) => Err(err),
+            Err(err) => Err(ReadStoreError::StoreError(WriteStoreError::StoreError(err))),
+        }
+    }
+}
+
+/// Returned by a failed compare and swap, holding the record that was actually stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompareAndSwapError<R> {
+    pub current: Option<R>,
+}
+
+impl<R> Display for CompareAndSwapError<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Compare and swap conflict")
+    }
+}
+
+impl<R: std::fmt::Debug> std::error::Error for CompareAndSwapError<R> {}
+
+/// A store that can atomically replace a record, but only if it still holds an expected value
+pub trait CompareAndSwapStore<R: Record> {
+    type Error: std::error::Error;
+
+    /// Replaces the record stored under `key` with `new` (removing it on `None`) if the stored record encodes to the
+    /// same value as `expected` (or is absent, if `expected` is `None`). On conflict, nothing is written and the
+    /// current record is returned instead. Both records must have the key `key`, or the swap fails with
+    /// [`WriteStoreError::KeyMismatch`].
+    #[allow(clippy::type_complexity)]
+    fn compare_and_swap(
+        self,
+        key: &R::Key,
+        expected: Option<&R>,
+        new: Option<&R>,
+    ) -> Result<Result<(), CompareAndSwapError<R>>, RecordUpdateError<R, Self::Error>>;
+}
+
+impl<R: Record, S: RawUpdateStore> CompareAndSwapStore<R> for S {
+    type Error = S::Error;
+
+    fn compare_and_swap(
+        self,
+        key: &R::Key,
+        expected: Option<&R>,
+        new: Option<&R>,
+    ) -> Result<Result<(), CompareAndSwapError<R>>, RecordUpdateError<R, Self::Error>> {
+        if missing_table::<R>() {
+            return Err(ReadStoreError::StoreError(WriteStoreError::NoTable));
+        }
+
+        let primary = key.encode();
+        let primary = primary.as_ref();
+
+        let encode = |record: Option<&R>| match record.map(R::try_encode).transpose() {
+            Ok(Some((record_key, _))) if record_key.encode().as_ref() != primary => {
+                Err(ReadStoreError::StoreError(WriteStoreError::KeyMismatch))
+            }
+            Ok(data) => Ok(data.map(|(_, value)| value)),
+            Err(err) => Err(ReadStoreError::StoreError(WriteStoreError::EncodeError(
+                err,
+            ))),
+        };
+
+        let entries = |record: Option<&R>| {
+            record.map_or_else(Vec::new, |record| index_entries(record, primary))
+        };
+
+        let old = entries(expected);
+        let new_entries = entries(new);
+        let expected = encode(expected)?;
+        let new = encode(new)?;
+
+        let result = RawUpdateStore::update(self, &table_key::<R>(primary), |current, batch| {
+            if current == expected.as_deref() {
+                index_writes::<R>(primary, old.clone(), new_entries.clone(), batch);
+                Ok(new.clone())
+            } else {
+                Err(SwapFailure::Conflict(current.map(<[u8]>::to_vec)))
+            }
+        });
+
+        match result {
+            Ok(Ok(())) => Ok(Ok(())),
+            Ok(Err(SwapFailure::Conflict(None))) => Ok(Err(CompareAndSwapError { current: None })),
+            Ok(Err(SwapFailure::Conflict(Some(value)))) => {
+                match R::try_decode(key.clone(), &value) {
+                    Ok(record) => Ok(Err(CompareAndSwapError {
+                        current: Some(record),
+                    })),
+                    Err(err) => Err(ReadStoreError::ValueDecodeError(err)),
+                }
+            }
+            Ok(Err(SwapFailure::UniqueViolation(err))) => Err(err.into()),
+            Err(err) => Err(ReadStoreError::StoreError(WriteStoreError::StoreError(err))),
+        }
+    }
+}
+
+/// Why a compare and swap wrote nothing
+enum SwapFailure {
+    /// The stored value didn't match, and is returned instead
+    Conflict(Option<Vec<u8>>),
+    UniqueViolation(UniqueViolation),
+}
+
+impl From<UniqueViolation> for SwapFailure {
+    fn from(err: UniqueViolation) -> Self {
+        SwapFailure::UniqueViolation(err)
+    }
+}
+
+/// A store that can remove every record in a range of keys at once, along with their index entries
+pub trait RemoveRangeStore<R: Record> {
+    type Error: std::error::Error;
+
+    /// Removes every record in `range`, returning how many were removed
+    fn remove_range<P: PrefixKey<R::Key>>(
+        self,
+        range: impl RangeBounds<P>,
+    ) -> Result<usize, Self::Error>;
+    /// Removes every record starting with `prefix`, returning how many were removed
+    fn remove_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Result<usize, Self::Error>;
+}
+
+impl<R: Record, S: RawRemoveRangeStore> RemoveRangeStore<R> for S {
+    type Error = S::Error;
+
+    fn remove_range<P: PrefixKey<R::Key>>(
+        self,
+        range: impl RangeBounds<P>,
+    ) -> Result<usize, Self::Error> {
+        remove_indexed::<R, _>(self, as_slices(&encode_range::<R, _>(range)))
+    }
+
+    fn remove_prefix<P: PrefixKey<R::Key>>(self, prefix: &P) -> Result<usize, Self::Error> {
+        remove_indexed::<R, _>(self, as_slices(&encode_prefix::<R, _>(prefix)))
+    }
+}
+
+/// Removes a range of raw keys holding records of type `R`, along with the index entries of the removed records
+fn remove_indexed<R: Record, S: RawRemoveRangeStore>(
+    store: S,
+    range: (Bound<&[u8]>, Bound<&[u8]>),
+) -> Result<usize, S::Error> {
+    if R::indexes().is_empty() {
+        return store.remove_range(range);
+    }
+
+    store.remove_range_with(range, |key, value, batch| {
+        let primary = strip_table::<R>(key);
+
+        // Like a value that can't be decoded, a key that can't be decoded has no entries
+        if let Ok((key, _)) = <R::Key as DecodeKey>::try_decode(primary) {
+            index_writes::<R>(
+                primary,
+                value_entries::<R>(&key, primary, Some(value)),
+                Vec::new(),
+                batch,
+            );
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateStoreError<S: std::error::Error, V: std::error::Error> {
+    /// A record with the same key is already stored
+    AlreadyExists,
+    StoreError(S),
+    EncodeError(V),
+    UniqueViolation(UniqueViolation),
+    /// The record has indexes but no [`Record::TABLE`] to keep them apart from its records, so nothing was written
+    NoTable,
+}
+
+impl<S: std::error::Error, V: std::error::Error> Display for CreateStoreError<S, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreateStoreError::AlreadyExists => write!(f, "A record with this key already exists"),
+            CreateStoreError::StoreError(err) => Display::fmt(err, f),
+            CreateStoreError::EncodeError(err) => Display::fmt(err, f),
+            CreateStoreError::UniqueViolation(err) => Display::fmt(err, f),
+            CreateStoreError::NoTable => write!(f, "Records with indexes need a table"),
+        }
+    }
+}
+
+impl<S: std::error::Error, V: std::error::Error> std::error::Error for CreateStoreError<S, V> {}
+
+impl<S: std::error::Error, V: std::error::Error> From<UniqueViolation> for CreateStoreError<S, V> {
+    fn from(err: UniqueViolation) -> Self {
+        CreateStoreError::UniqueViolation(err)
+    }
+}
+
+/// A store that can atomically persist a record only if its key is absent
+pub trait CreateStore<R: Record> {
+    type Error: std::error::Error;
+
+    /// Persists a record, failing with [`CreateStoreError::AlreadyExists`] (and writing nothing) if a record with the
+    /// same key is already stored
+    fn create(self, record: &R) -> Result<(), CreateStoreError<Self::Error, R::EncodeError>>;
+}
+
+impl<R: Record, S: RawUpdateStore> CreateStore<R> for S {
+    type Error = S::Error;
+
+    fn create(self, record: &R) -> Result<(), CreateStoreError<Self::Error, R::EncodeError>> {
+        let (key, value) = match record.try_encode() {
+            Ok(data) => data,
+            Err(err) => return Err(CreateStoreError::EncodeError(err)),
+        };
+
+        if missing_table::<R>() {
+            return Err(CreateStoreError::NoTable);
+        }
+
+        let primary = key.encode();
+        let primary = primary.as_ref();
+        let entries = index_entries(record, primary);
+
+        let result =
+            RawUpdateStore::update(
+                self,
+                &table_key::<R>(primary),
+                |current, batch| match current {
+                    Some(_) => Err(CreateStoreError::AlreadyExists),
+                    None => {
+                        index_writes::<R>(primary, Vec::new(), entries.clone(), batch);
+                        Ok(Some(value.clone()))
+                    }
+                },
+            );
+
+        match result {
+            Ok(result) => result,
+            Err(err) => Err(CreateStoreError::StoreError(err)),
+        }
+    }
+}
+
+impl<R: Record, S: RawWriteStore> WriteStore<R> for S {
+    type Error = S::Error;
+
+    fn persist(self, record: &R) -> Result<(), WriteStoreError<Self::Error, R::EncodeError>> {
+        let (key, value) = match record.try_encode() {
+            Ok(data) => data,
+            Err(err) => return Err(WriteStoreError::EncodeError(err)),
+        };
+
+        let result = if R::indexes().is_empty() {
+            self.insert(encode_key::<R>(&key).as_slice(), value)
+        } else if R::TABLE.is_empty() {
+            return Err(WriteStoreError::NoTable);
+        } else {
+            let primary = key.encode();
+            let primary = primary.as_ref();
+            let entries = index_entries(record, primary);
+
+            match self.write_with(&table_key::<R>(primary), Some(value), |old, batch| {
+                index_writes::<R>(
+                    primary,
+                    value_entries::<R>(&key, primary, old),
+                    entries.clone(),
+                    batch,
+                )
+            }) {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(err)) => return Err(err.into()),
+                Err(err) => Err(err),
+            }
+        };
+
+        result.map_err(WriteStoreError::StoreError)
+    }
+
+    fn remove(self, key: &R::Key) -> Result<(), Self::Error> {
+        if R::indexes().is_empty() {
+            return RawWriteStore::remove(self, encode_key::<R>(key).as_slice());
+        }
+
+        let primary = key.encode();
+        let primary = primary.as_ref();
+
+        // Removing a record only removes index entries, so it can't violate a unique index, and stores that can't
+        // write index entries leave them behind for index scans to skip
+        let result = self.write_with(&table_key::<R>(primary), None, |old, batch| {
+            index_writes::<R>(
+                primary,
+                value_entries::<R>(key, primary, old),
+                Vec::new(),
+                batch,
+            )
+        });
+
+        result.map(|_| ())
+    }
+}
+
+/// The table the counters behind [`Sequence`] are stored in by in-memory stores, under the table of the record type
+/// they count. Sled generates ids without storing anything in a tree.
+///
+/// Like any other table, no [`Record::TABLE`] should be a prefix of it. Records without a table can't use an
+/// in-memory [`Sequence`], since removing a range of them could remove the counter and hand out ids again.
+pub const SEQUENCE_TABLE: &[u8] = b"bobsled/sequence/";
+
+/// A source of unique, increasing ids for records of type `R`, for use as their keys.
+///
+/// Ids are never handed out twice, but needn't be contiguous: sled shares one generator between every record type,
+/// skips ahead after a crash, and may skip ids generated by a transaction that was retried.
+pub trait Sequence<R: Record> {
+    type Error: std::error::Error;
+
+    /// Returns the next id, advancing the sequence
+    fn next_id(&mut self) -> Result<Result<u64, SequenceError>, Self::Error>;
+}
+
+/// An error generating an id from a [`Sequence`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError {
+    /// The record type has no [`Record::TABLE`] to keep its counter apart from its records
+    NoTable,
+    /// The counter holds something other than an id
+    InvalidCounter,
+    /// Every id has been handed out
+    Exhausted,
+}
+
+impl Display for SequenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SequenceError::NoTable => write!(f, "Records without a table can't have a sequence"),
+            SequenceError::InvalidCounter => write!(f, "The sequence counter is invalid"),
+            SequenceError::Exhausted => write!(f, "The sequence has run out of ids"),
+        }
+    }
+}
+
+impl std::error::Error for SequenceError {}
+
+/// An error returned by [`Record::insert_auto`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertAutoError<S: std::error::Error, V: std::error::Error> {
+    /// No id could be generated for the record
+    SequenceError(SequenceError),
+    CreateError(CreateStoreError<S, V>),
+}
+
+impl<S: std::error::Error, V: std::error::Error> Display for InsertAutoError<S, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InsertAutoError::SequenceError(err) => Display::fmt(err, f),
+            InsertAutoError::CreateError(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl<S: std::error::Error, V: std::error::Error> std::error::Error for InsertAutoError<S, V> {}
+
+/// Returns the raw key of the counter behind the in-memory [`Sequence`] of `R`, if it has a table
+fn sequence_key<R: Record>() -> Result<Vec<u8>, SequenceError> {
+    if R::TABLE.is_empty() {
+        return Err(SequenceError::NoTable);
+    }
+
+    let mut bytes = Vec::with_capacity(SEQUENCE_TABLE.len() + R::TABLE.len());
+    bytes.extend_from_slice(SEQUENCE_TABLE);
+    bytes.extend_from_slice(R::TABLE);
+
+    Ok(bytes)
+}
+
+/// Returns the id stored in a counter, and the value the counter should be updated to
+fn next_counter(counter: Option<&[u8]>) -> Result<(u64, Vec<u8>), SequenceError> {
+    let id = match counter.map(TryInto::try_into) {
+        Some(Ok(bytes)) => u64::from_be_bytes(bytes),
+        Some(Err(_)) => return Err(SequenceError::InvalidCounter),
+        None => 0,
+    };
+
+    match id.checked_add(1) {
+        Some(next) => Ok((id, next.to_be_bytes().to_vec())),
+        None => Err(SequenceError::Exhausted),
+    }
+}
+
+mod batch;
+pub use batch::RecordBatch;
+
+mod btree;
+pub use btree::*;
+
+mod hash;
+
+mod index;
+use index::{index_entries, index_writes, missing_table, value_entries, Entry};
+pub use index::{Index, IndexDef, IndexIter, IndexStore, IndexWriteError, UniqueViolation};
+
+mod page;
+pub use page::{Cursor, Page};
+
+mod raw;
+pub use raw::*;
+
+#[cfg(feature = "sled")]
+mod sled;
+
+#[cfg(feature = "sled")]
+mod tables;
+#[cfg(feature = "sled")]
+pub use tables::{Tables, Transaction, TransactionError, UnregisteredTable};
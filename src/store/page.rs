@@ -0,0 +1,74 @@
+use std::ops::Bound;
+
+/// An opaque position within a scan, used to resume it with the record that follows.
+///
+/// Holds the raw encoded key of the last record returned, so it stays valid even if records are inserted or removed
+/// between calls.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cursor(Vec<u8>);
+
+impl Cursor {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Encodes the cursor as URL-safe base64, suitable for use in query strings
+    #[cfg(feature = "base64")]
+    pub fn to_base64(&self) -> String {
+        use base64::Engine;
+
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&self.0)
+    }
+
+    #[cfg(feature = "base64")]
+    pub fn from_base64(encoded: impl AsRef<[u8]>) -> Result<Self, base64::DecodeError> {
+        use base64::Engine;
+
+        base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map(Self)
+    }
+}
+
+/// A page of records, along with the cursor to fetch the next page (if there are more records)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<R> {
+    pub records: Vec<R>,
+    pub next: Option<Cursor>,
+}
+
+/// Moves the start of a range past a cursor, unless the range already starts after it. Returns `None` if the cursor
+/// is at or past the end of the range, so there's nothing left to scan (cursors come from clients, so this isn't
+/// necessarily a cursor this range handed out).
+pub(crate) fn resume(
+    start: Bound<Vec<u8>>,
+    end: &Bound<Vec<u8>>,
+    after: Option<&Cursor>,
+) -> Option<Bound<Vec<u8>>> {
+    let after = match after {
+        Some(after) => after.as_bytes(),
+        None => return Some(start),
+    };
+
+    let starts_after = match &start {
+        Bound::Included(start) | Bound::Excluded(start) => start.as_slice() > after,
+        Bound::Unbounded => false,
+    };
+
+    if starts_after {
+        return Some(start);
+    }
+
+    match end {
+        Bound::Included(end) | Bound::Excluded(end) if after >= end.as_slice() => None,
+        _ => Some(Bound::Excluded(after.to_owned())),
+    }
+}
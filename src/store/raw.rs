@@ -0,0 +1,216 @@
+use std::{borrow::Cow, ops::Bound};
+
+/// A store that can look up raw values by their encoded key.
+///
+/// Implementing this (and optionally [`RawScanStore`] and [`RawWriteStore`]) is all that's needed to use a new
+/// backend, since the typed [`PointReadStore`](crate::PointReadStore), [`ScanStore`](crate::ScanStore) and
+/// [`WriteStore`](crate::WriteStore) traits are implemented for every raw store.
+pub trait RawStore {
+    type Error: std::error::Error;
+    type Value: AsRef<[u8]>;
+
+    fn get(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error>;
+
+    fn contains_key(self, key: &[u8]) -> Result<bool, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.get(key).map(|value| value.is_some())
+    }
+}
+
+/// A store that can iterate (in either direction) over a range of raw keys in lexicographic order
+pub trait RawScanStore: RawStore {
+    type Key: AsRef<[u8]>;
+    type Range: DoubleEndedIterator<Item = Result<(Self::Key, Self::Value), Self::Error>>;
+
+    fn range(self, range: (Bound<&[u8]>, Bound<&[u8]>)) -> Self::Range;
+
+    /// Counts the entries in a range. Backends that can count without visiting every entry should override this.
+    fn count(self, range: (Bound<&[u8]>, Bound<&[u8]>)) -> Result<usize, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.range(range)
+            .try_fold(0, |count, entry| entry.map(|_| count + 1))
+    }
+
+    /// Estimates the size in bytes of the entries in a range, as the total length of their keys and values.
+    /// Backends that can estimate without visiting every entry should override this.
+    fn size(self, range: (Bound<&[u8]>, Bound<&[u8]>)) -> Result<u64, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.range(range).try_fold(0, |size, entry| {
+            entry.map(|(key, value)| size + (key.as_ref().len() + value.as_ref().len()) as u64)
+        })
+    }
+}
+
+/// A store that can insert and remove raw values by their encoded key
+pub trait RawWriteStore {
+    type Error: std::error::Error;
+
+    fn insert(self, key: &[u8], value: Vec<u8>) -> Result<(), Self::Error>;
+    fn remove(self, key: &[u8]) -> Result<(), Self::Error>;
+
+    /// Writes `value` under `key` (removing it on `None`), along with any writes `f` adds to the batch it's passed,
+    /// based on the value being replaced. This is how secondary indexes are kept in sync, so stores should apply
+    /// every write atomically where they can. Write-only stores (like sled's `Batch`) pass `None` to `f`.
+    ///
+    /// If the batch would violate a unique index, nothing is written.
+    ///
+    /// The default implementation can only write `key` itself, so it passes `None` to `f` and fails with
+    /// [`IndexWriteError::Unsupported`](crate::IndexWriteError::Unsupported) (writing nothing) if `f` adds any writes.
+    /// Stores that implement [`RawUpdateStore`] can override it with [`write_with_update`].
+    fn write_with(
+        self,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+        mut f: impl FnMut(Option<&[u8]>, &mut crate::RecordBatch),
+    ) -> Result<Result<(), crate::IndexWriteError>, Self::Error>
+    where
+        Self: Sized,
+    {
+        let mut batch = crate::RecordBatch::new();
+        f(None, &mut batch);
+        if !batch.is_empty() {
+            return Ok(Err(crate::IndexWriteError::Unsupported));
+        }
+
+        match value {
+            Some(value) => self.insert(key, value),
+            None => self.remove(key),
+        }
+        .map(Ok)
+    }
+}
+
+/// Implements [`RawWriteStore::write_with`] as an update that ignores the value it replaces
+pub fn write_with_update<S: RawUpdateStore>(
+    store: S,
+    key: &[u8],
+    value: Option<Vec<u8>>,
+    mut f: impl FnMut(Option<&[u8]>, &mut crate::RecordBatch),
+) -> Result<Result<(), crate::IndexWriteError>, S::Error> {
+    store.update(key, |current, batch| {
+        f(current, batch);
+
+        Ok(value.clone())
+    })
+}
+
+/// A store that can apply a [`RecordBatch`](crate::RecordBatch) atomically
+pub trait RawBatchStore {
+    type Error: std::error::Error;
+
+    /// Applies every write in `batch` once it's been [prepared](crate::RecordBatch::prepare), or fails with a
+    /// [`UniqueViolation`](crate::UniqueViolation) (writing nothing) if preparing it does
+    fn apply_batch(
+        self,
+        batch: crate::RecordBatch,
+    ) -> Result<Result<(), crate::UniqueViolation>, Self::Error>;
+}
+
+/// A store that can remove every raw value in a range of keys at once
+pub trait RawRemoveRangeStore {
+    type Error: std::error::Error;
+
+    /// Removes every entry in a range, returning how many were removed
+    fn remove_range(self, range: (Bound<&[u8]>, Bound<&[u8]>)) -> Result<usize, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.remove_range_with(range, |_, _, _| ())
+    }
+
+    /// Removes every entry in a range along with any writes `f` adds to the batch it's passed, given the key and value
+    /// of each removed entry, returning how many entries were removed. This is how the index entries of the removed
+    /// records are removed too, so stores should apply every write atomically where they can.
+    fn remove_range_with(
+        self,
+        range: (Bound<&[u8]>, Bound<&[u8]>),
+        f: impl FnMut(&[u8], &[u8], &mut crate::RecordBatch),
+    ) -> Result<usize, Self::Error>;
+}
+
+/// A store that can insert and remove raw values by their encoded key, returning the value they replaced.
+///
+/// Unlike [`RawWriteStore`] this isn't implemented for write-only stores (like sled's `Batch`), which never see the
+/// previous value.
+pub trait RawReplaceStore {
+    type Error: std::error::Error;
+    type Value: AsRef<[u8]>;
+
+    fn replace(self, key: &[u8], value: Vec<u8>) -> Result<Option<Self::Value>, Self::Error>;
+    fn take(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error>;
+}
+
+/// A store that can atomically replace a raw value based on its current value
+pub trait RawUpdateStore {
+    type Error: std::error::Error;
+
+    /// Replaces the value stored under `key` (removing it on `None`) with the result of `f`, which is passed the
+    /// current value. `f` may be called more than once if the value is modified concurrently.
+    /// If `f` fails, nothing is written and its error is returned.
+    ///
+    /// `f` may also add writes to other keys to the batch it's passed (e.g. to keep indexes in sync), which are
+    /// applied atomically along with the new value. If the batch would violate a unique index, nothing is written.
+    fn update<E: From<crate::UniqueViolation>>(
+        self,
+        key: &[u8],
+        f: impl FnMut(Option<&[u8]>, &mut crate::RecordBatch) -> Result<Option<Vec<u8>>, E>,
+    ) -> Result<Result<(), E>, Self::Error>;
+}
+
+/// A store that nothing else can write to while it's in use (like `&mut BTreeMap`, or a transaction), so reading a
+/// value and then applying a batch is already atomic.
+///
+/// [`RawWriteStore`] and [`RawUpdateStore`] are implemented for every exclusive store.
+pub trait RawExclusiveStore: RawBatchStore {
+    /// Reads the current value stored under `key`
+    fn read(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Self::Error>;
+}
+
+impl<S: RawExclusiveStore> RawWriteStore for S {
+    type Error = S::Error;
+
+    fn insert(self, key: &[u8], value: Vec<u8>) -> Result<(), Self::Error> {
+        let mut batch = crate::RecordBatch::new();
+        batch.push(key.into(), Some(value));
+
+        // A batch that claims no index entries can't violate a unique index
+        self.apply_batch(batch).map(|_| ())
+    }
+
+    fn remove(self, key: &[u8]) -> Result<(), Self::Error> {
+        let mut batch = crate::RecordBatch::new();
+        batch.push(key.into(), None);
+
+        self.apply_batch(batch).map(|_| ())
+    }
+
+    fn write_with(
+        self,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+        f: impl FnMut(Option<&[u8]>, &mut crate::RecordBatch),
+    ) -> Result<Result<(), crate::IndexWriteError>, Self::Error> {
+        write_with_update(self, key, value, f)
+    }
+}
+
+impl<S: RawExclusiveStore> RawUpdateStore for S {
+    type Error = S::Error;
+
+    fn update<E: From<crate::UniqueViolation>>(
+        self,
+        key: &[u8],
+        f: impl FnMut(Option<&[u8]>, &mut crate::RecordBatch) -> Result<Option<Vec<u8>>, E>,
+    ) -> Result<Result<(), E>, Self::Error> {
+        match crate::RecordBatch::prepare_update(key, |key| self.read(key), f)? {
+            Ok(batch) => Ok(self.apply_batch(batch)?.map_err(E::from)),
+            Err(err) => Ok(Err(err)),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::{borrow::Cow, cell::RefCell, convert::Infallible, ops::Bound};
+
+use sled::{
+    transaction::{
+        ConflictableTransactionError, TransactionError, TransactionalTree,
+        UnabortableTransactionError,
+    },
+    Batch, Db, IVec, Transactional, Tree,
+};
+
+use super::{
+    write_with_update, CreateStore, CreateStoreError, IndexWriteError, RawBatchStore,
+    RawExclusiveStore, RawRemoveRangeStore, RawReplaceStore, RawScanStore, RawStore,
+    RawUpdateStore, RawWriteStore, RecordBatch, Sequence, SequenceError, UniqueViolation,
+};
+use crate::Record;
+
+fn into_batch(batch: RecordBatch) -> Batch {
+    let mut sled_batch = Batch::default();
+
+    for (key, value) in batch {
+        match value {
+            Some(value) => sled_batch.insert(key, value),
+            None => sled_batch.remove(key),
+        }
+    }
+
+    sled_batch
+}
+
+impl RawStore for &Tree {
+    type Error = sled::Error;
+    type Value = IVec;
+
+    fn get(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Tree::get(self, key)
+    }
+
+    fn contains_key(self, key: &[u8]) -> Result<bool, Self::Error> {
+        Tree::contains_key(self, key)
+    }
+}
+
+impl RawScanStore for &Tree {
+    type Key = IVec;
+    type Range = sled::Iter;
+
+    fn range(self, range: (Bound<&[u8]>, Bound<&[u8]>)) -> Self::Range {
+        Tree::range::<&[u8], _>(self, range)
+    }
+}
+
+impl RawWriteStore for &Tree {
+    type Error = sled::Error;
+
+    fn insert(self, key: &[u8], value: Vec<u8>) -> Result<(), Self::Error> {
+        Tree::insert(self, key, value).and(Ok(()))
+    }
+
+    fn remove(self, key: &[u8]) -> Result<(), Self::Error> {
+        Tree::remove(self, key).and(Ok(()))
+    }
+
+    fn write_with(
+        self,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+        f: impl FnMut(Option<&[u8]>, &mut RecordBatch),
+    ) -> Result<Result<(), IndexWriteError>, Self::Error> {
+        write_with_update(self, key, value, f)
+    }
+}
+
+// Transactions can't iterate, so the keys in the range are read up front and then removed in a single transaction,
+// which computes the index writes from each record's value at that point. Keys inserted into the range in the
+// meantime aren't removed.
+impl RawRemoveRangeStore for &Tree {
+    type Error = sled::Error;
+
+    fn remove_range_with(
+        self,
+        range: (Bound<&[u8]>, Bound<&[u8]>),
+        f: impl FnMut(&[u8], &[u8], &mut RecordBatch),
+    ) -> Result<usize, Self::Error> {
+        let keys = Tree::range::<&[u8], _>(self, range)
+            .keys()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Transactions take an `Fn`, but will only ever call it from one thread at a time
+        let f = RefCell::new(f);
+
+        let result = <&Tree as Transactional<Infallible>>::transaction(&self, |tx| {
+            let mut batch = RecordBatch::new();
+            let mut count = 0;
+
+            for key in &keys {
+                if let Some(value) = tx.get(key)? {
+                    (*f.borrow_mut())(key, &value, &mut batch);
+                    batch.push(key.to_vec(), None);
+                    count += 1;
+                }
+            }
+
+            tx.apply_batch(&into_batch(batch))?;
+
+            Ok(count)
+        });
+
+        match result {
+            Ok(count) => Ok(count),
+            Err(TransactionError::Storage(err)) => Err(err),
+            Err(TransactionError::Abort(never)) => match never {},
+        }
+    }
+}
+
+// Preparing a batch means reading the tree, so a batch that needs it is applied in a transaction instead
+impl RawBatchStore for &Tree {
+    type Error = sled::Error;
+
+    fn apply_batch(self, batch: RecordBatch) -> Result<Result<(), UniqueViolation>, Self::Error> {
+        if !batch.needs_prepare() {
+            return Tree::apply_batch(self, into_batch(batch)).map(Ok);
+        }
+
+        let result = <&Tree as Transactional<Infallible>>::transaction(&self, |tx| {
+            Ok(RawBatchStore::apply_batch(tx, batch.clone())?)
+        });
+
+        match result {
+            Ok(result) => Ok(result),
+            Err(TransactionError::Storage(err)) => Err(err),
+            Err(TransactionError::Abort(never)) => match never {},
+        }
+    }
+}
+
+impl RawReplaceStore for &Tree {
+    type Error = sled::Error;
+    type Value = IVec;
+
+    fn replace(self, key: &[u8], value: Vec<u8>) -> Result<Option<Self::Value>, Self::Error> {
+        Tree::insert(self, key, value)
+    }
+
+    fn take(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Tree::remove(self, key)
+    }
+}
+
+impl RawUpdateStore for &Tree {
+    type Error = sled::Error;
+
+    fn update<E: From<UniqueViolation>>(
+        self,
+        key: &[u8],
+        mut f: impl FnMut(Option<&[u8]>, &mut RecordBatch) -> Result<Option<Vec<u8>>, E>,
+    ) -> Result<Result<(), E>, Self::Error> {
+        let mut current = Tree::get(self, key)?;
+
+        loop {
+            let mut batch = RecordBatch::new();
+            let new = match f(current.as_deref(), &mut batch) {
+                Ok(new) => new,
+                Err(err) => return Ok(Err(err)),
+            };
+
+            // A compare and swap can only write a single key, so anything more needs a transaction
+            if !batch.is_empty() {
+                return update_in_transaction(self, key, f);
+            }
+
+            match Tree::compare_and_swap(self, key, current.as_ref(), new)? {
+                Ok(()) => return Ok(Ok(())),
+                Err(conflict) => current = conflict.current,
+            }
+        }
+    }
+}
+
+/// Runs an update that writes to more than one key in a transaction over `tree`
+fn update_in_transaction<E: From<UniqueViolation>>(
+    tree: &Tree,
+    key: &[u8],
+    f: impl FnMut(Option<&[u8]>, &mut RecordBatch) -> Result<Option<Vec<u8>>, E>,
+) -> Result<Result<(), E>, sled::Error> {
+    // Transactions take an `Fn`, but will only ever call it from one thread at a time
+    let f = RefCell::new(f);
+
+    let result =
+        <&Tree as Transactional<Infallible>>::transaction(
+            &tree,
+            |tx| match RawUpdateStore::update(tx, key, &mut *f.borrow_mut()) {
+                Ok(result) => Ok(result),
+                Err(err) => Err(ConflictableTransactionError::from(err)),
+            },
+        );
+
+    match result {
+        Ok(result) => Ok(result),
+        Err(TransactionError::Storage(err)) => Err(err),
+        Err(TransactionError::Abort(never)) => match never {},
+    }
+}
+
+// Sled transactions have no way to iterate over the tree, so they don't implement RawScanStore
+impl RawStore for &TransactionalTree {
+    type Error = sled::transaction::UnabortableTransactionError;
+    type Value = IVec;
+
+    fn get(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        TransactionalTree::get(self, key)
+    }
+}
+
+// Transactions are already isolated, so updates needn't retry
+impl RawExclusiveStore for &TransactionalTree {
+    fn read(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, UnabortableTransactionError> {
+        TransactionalTree::get(self, key).map(|value| value.map(|value| Cow::Owned(value.to_vec())))
+    }
+}
+
+impl RawBatchStore for &TransactionalTree {
+    type Error = sled::transaction::UnabortableTransactionError;
+
+    fn apply_batch(self, batch: RecordBatch) -> Result<Result<(), UniqueViolation>, Self::Error> {
+        let batch = match batch.prepare(|key| TransactionalTree::get(self, key))? {
+            Ok(batch) => batch,
+            Err(err) => return Ok(Err(err)),
+        };
+
+        TransactionalTree::apply_batch(self, &into_batch(batch)).map(Ok)
+    }
+}
+
+impl RawReplaceStore for &TransactionalTree {
+    type Error = sled::transaction::UnabortableTransactionError;
+    type Value = IVec;
+
+    fn replace(self, key: &[u8], value: Vec<u8>) -> Result<Option<Self::Value>, Self::Error> {
+        TransactionalTree::insert(self, key, value)
+    }
+
+    fn take(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        TransactionalTree::remove(self, key)
+    }
+}
+
+// Ids are generated by the database rather than stored in a tree, so a plain `Tree` can't generate them
+impl<R: Record> Sequence<R> for &Db {
+    type Error = sled::Error;
+
+    fn next_id(&mut self) -> Result<Result<u64, SequenceError>, Self::Error> {
+        Db::generate_id(self).map(Ok)
+    }
+}
+
+// A tree can't generate ids itself, so it's paired with its database to create records with generated ids
+impl<R: Record> Sequence<R> for (&Db, &Tree) {
+    type Error = sled::Error;
+
+    fn next_id(&mut self) -> Result<Result<u64, SequenceError>, Self::Error> {
+        Db::generate_id(self.0).map(Ok)
+    }
+}
+
+impl<R: Record> CreateStore<R> for (&Db, &Tree) {
+    type Error = sled::Error;
+
+    fn create(self, record: &R) -> Result<(), CreateStoreError<Self::Error, R::EncodeError>> {
+        CreateStore::create(self.1, record)
+    }
+}
+
+impl<R: Record> Sequence<R> for &TransactionalTree {
+    type Error = UnabortableTransactionError;
+
+    fn next_id(&mut self) -> Result<Result<u64, SequenceError>, Self::Error> {
+        TransactionalTree::generate_id(self)
+            .map(Ok)
+            .map_err(UnabortableTransactionError::Storage)
+    }
+}
+
+impl RawWriteStore for &mut Batch {
+    type Error = Infallible;
+
+    fn insert(self, key: &[u8], value: Vec<u8>) -> Result<(), Self::Error> {
+        Batch::insert(self, key, value);
+
+        Ok(())
+    }
+
+    fn remove(self, key: &[u8]) -> Result<(), Self::Error> {
+        Batch::remove(self, key);
+
+        Ok(())
+    }
+
+    fn write_with(
+        self,
+        key: &[u8],
+        value: Option<Vec<u8>>,
+        mut f: impl FnMut(Option<&[u8]>, &mut RecordBatch),
+    ) -> Result<Result<(), IndexWriteError>, Self::Error> {
+        // The batch can't see the record being replaced, so that record's index entries are left behind for index
+        // scans to skip
+        let mut batch = RecordBatch::new();
+        f(None, &mut batch);
+
+        // Sled applies the batch itself, so there's no chance to check unique indexes
+        if batch.needs_prepare() {
+            return Ok(Err(IndexWriteError::Unsupported));
+        }
+
+        match value {
+            Some(value) => Batch::insert(self, key, value),
+            None => Batch::remove(self, key),
+        }
+
+        for (key, value) in batch {
+            match value {
+                Some(value) => Batch::insert(self, key, value),
+                None => Batch::remove(self, key),
+            }
+        }
+
+        Ok(Ok(()))
+    }
+}
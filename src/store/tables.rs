@@ -0,0 +1,237 @@
+use std::{any::TypeId, collections::HashMap, fmt::Display};
+
+use sled::{
+    transaction::{ConflictableTransactionError, TransactionalTree, UnabortableTransactionError},
+    Transactional, Tree,
+};
+
+use super::{CreateStoreError, InsertAutoError, ReadStoreError, WriteStoreError};
+use crate::Record;
+
+/// Maps each [`Record`] type to the sled tree it's stored in, so that records of many types can be read and written
+/// in a single transaction.
+///
+/// Every tree must belong to the same database.
+#[derive(Debug, Clone, Default)]
+pub struct Tables {
+    trees: Vec<Tree>,
+    indices: HashMap<TypeId, usize>,
+}
+
+impl Tables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores records of type `R` in `tree`, replacing any tree previously registered for `R`
+    pub fn with<R: Record + 'static>(mut self, tree: Tree) -> Self {
+        match self.indices.get(&TypeId::of::<R>()) {
+            Some(&index) => self.trees[index] = tree,
+            None => {
+                self.indices.insert(TypeId::of::<R>(), self.trees.len());
+                self.trees.push(tree);
+            }
+        }
+
+        self
+    }
+
+    /// Returns the tree records of type `R` are stored in, for use outside of a transaction
+    pub fn tree<R: Record + 'static>(&self) -> Option<&Tree> {
+        self.indices
+            .get(&TypeId::of::<R>())
+            .map(|&index| &self.trees[index])
+    }
+
+    /// Runs `f` in a transaction spanning every registered tree, retrying it if it conflicts with another transaction.
+    ///
+    /// Errors from any store operation can be propagated with `?`, and the closure can abort the transaction by
+    /// returning [`TransactionError::Abort`]; either way, none of its writes are applied.
+    pub fn transaction<A, E, F>(&self, f: F) -> Result<A, TransactionError<E>>
+    where
+        F: Fn(&Transaction<'_>) -> Result<A, TransactionError<E>>,
+    {
+        // Sled can't run a transaction over no trees, but then there's nothing to isolate either
+        if self.trees.is_empty() {
+            return f(&Transaction {
+                trees: &[],
+                indices: &self.indices,
+            });
+        }
+
+        let result = self.trees.as_slice().transaction(|trees| {
+            let tx = Transaction {
+                trees,
+                indices: &self.indices,
+            };
+
+            match f(&tx) {
+                Ok(value) => Ok(value),
+                Err(TransactionError::Conflict) => Err(ConflictableTransactionError::Conflict),
+                Err(TransactionError::Storage(err)) => {
+                    Err(ConflictableTransactionError::Storage(err))
+                }
+                Err(err) => Err(ConflictableTransactionError::Abort(err)),
+            }
+        });
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(sled::transaction::TransactionError::Abort(err)) => Err(err),
+            Err(sled::transaction::TransactionError::Storage(err)) => {
+                Err(TransactionError::Storage(err))
+            }
+        }
+    }
+}
+
+/// The view of a [`Tables`] transaction passed to its closure
+pub struct Transaction<'a> {
+    trees: &'a [TransactionalTree],
+    indices: &'a HashMap<TypeId, usize>,
+}
+
+impl Transaction<'_> {
+    /// Returns the transactional view of the tree records of type `R` are stored in, or fails with
+    /// [`UnregisteredTable`] (which converts into [`TransactionError::Record`]) if no tree was registered for `R`.
+    pub fn table<R: Record + 'static>(&self) -> Result<&TransactionalTree, UnregisteredTable> {
+        match self.indices.get(&TypeId::of::<R>()) {
+            Some(&index) => Ok(&self.trees[index]),
+            None => Err(UnregisteredTable {
+                record: std::any::type_name::<R>(),
+            }),
+        }
+    }
+}
+
+/// The error returned by [`Transaction::table`] for a record type that has no registered tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnregisteredTable {
+    /// The name of the record type
+    pub record: &'static str,
+}
+
+impl Display for UnregisteredTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No tree was registered for {}", self.record)
+    }
+}
+
+impl std::error::Error for UnregisteredTable {}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The error produced by a [`Tables`] transaction, and by the closure it runs
+#[derive(Debug)]
+pub enum TransactionError<E> {
+    /// The closure aborted the transaction
+    Abort(E),
+    /// A record couldn't be encoded, decoded or otherwise written
+    Record(BoxError),
+    /// The underlying storage failed
+    Storage(sled::Error),
+    /// The transaction conflicted with another one and will be retried. This is never returned from
+    /// [`Tables::transaction`], and shouldn't be returned from the closure directly.
+    #[doc(hidden)]
+    Conflict,
+}
+
+impl<E: Display> Display for TransactionError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionError::Abort(err) => Display::fmt(err, f),
+            TransactionError::Record(err) => Display::fmt(err, f),
+            TransactionError::Storage(err) => Display::fmt(err, f),
+            TransactionError::Conflict => write!(f, "Conflict during transaction"),
+        }
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for TransactionError<E> {}
+
+impl<E> From<UnabortableTransactionError> for TransactionError<E> {
+    fn from(err: UnabortableTransactionError) -> Self {
+        match err {
+            UnabortableTransactionError::Conflict => TransactionError::Conflict,
+            UnabortableTransactionError::Storage(err) => TransactionError::Storage(err),
+        }
+    }
+}
+
+impl<E> From<UnregisteredTable> for TransactionError<E> {
+    fn from(err: UnregisteredTable) -> Self {
+        TransactionError::Record(Box::new(err))
+    }
+}
+
+impl<E, K, V> From<ReadStoreError<UnabortableTransactionError, K, V>> for TransactionError<E>
+where
+    K: std::error::Error + Send + Sync + 'static,
+    V: std::error::Error + Send + Sync + 'static,
+{
+    fn from(err: ReadStoreError<UnabortableTransactionError, K, V>) -> Self {
+        match err {
+            ReadStoreError::StoreError(err) => err.into(),
+            ReadStoreError::KeyDecodeErr(err) => TransactionError::Record(Box::new(err)),
+            ReadStoreError::ValueDecodeError(err) => TransactionError::Record(Box::new(err)),
+            err @ ReadStoreError::ZeroLimit => TransactionError::Record(Box::new(err)),
+        }
+    }
+}
+
+impl<E, V> From<WriteStoreError<UnabortableTransactionError, V>> for TransactionError<E>
+where
+    V: std::error::Error + Send + Sync + 'static,
+{
+    fn from(err: WriteStoreError<UnabortableTransactionError, V>) -> Self {
+        match err {
+            WriteStoreError::StoreError(err) => err.into(),
+            WriteStoreError::EncodeError(err) => TransactionError::Record(Box::new(err)),
+            WriteStoreError::UniqueViolation(err) => TransactionError::Record(Box::new(err)),
+            err @ WriteStoreError::KeyMismatch => TransactionError::Record(Box::new(err)),
+            err @ WriteStoreError::IndexUnsupported => TransactionError::Record(Box::new(err)),
+            err @ WriteStoreError::NoTable => TransactionError::Record(Box::new(err)),
+        }
+    }
+}
+
+impl<E, K, V, W> From<ReadStoreError<WriteStoreError<UnabortableTransactionError, W>, K, V>>
+    for TransactionError<E>
+where
+    K: std::error::Error + Send + Sync + 'static,
+    V: std::error::Error + Send + Sync + 'static,
+    W: std::error::Error + Send + Sync + 'static,
+{
+    fn from(err: ReadStoreError<WriteStoreError<UnabortableTransactionError, W>, K, V>) -> Self {
+        match err {
+            ReadStoreError::StoreError(err) => err.into(),
+            ReadStoreError::KeyDecodeErr(err) => TransactionError::Record(Box::new(err)),
+            ReadStoreError::ValueDecodeError(err) => TransactionError::Record(Box::new(err)),
+            err @ ReadStoreError::ZeroLimit => TransactionError::Record(Box::new(err)),
+        }
+    }
+}
+
+impl<E, V> From<CreateStoreError<UnabortableTransactionError, V>> for TransactionError<E>
+where
+    V: std::error::Error + Send + Sync + 'static,
+{
+    fn from(err: CreateStoreError<UnabortableTransactionError, V>) -> Self {
+        match err {
+            CreateStoreError::StoreError(err) => err.into(),
+            err => TransactionError::Record(Box::new(err)),
+        }
+    }
+}
+
+impl<E, V> From<InsertAutoError<UnabortableTransactionError, V>> for TransactionError<E>
+where
+    V: std::error::Error + Send + Sync + 'static,
+{
+    fn from(err: InsertAutoError<UnabortableTransactionError, V>) -> Self {
+        match err {
+            InsertAutoError::SequenceError(err) => TransactionError::Record(Box::new(err)),
+            InsertAutoError::CreateError(err) => err.into(),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use bobsled::{DecodeKey, EncodeKey, Record};
+
+#[derive(Debug, Clone, PartialEq, EncodeKey, DecodeKey)]
+struct OrderKey {
+    customer: u64,
+    region: String,
+    date: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Record)]
+struct Order(#[bobsled(key)] OrderKey);
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, EncodeKey, DecodeKey)]
+enum Priority {
+    Low,
+    Normal(u8),
+    High { level: i16, escalated: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq, EncodeKey, DecodeKey)]
+struct Wrapper<T>(T);
+
+#[test]
+fn derive_struct_key() {
+    let key = OrderKey {
+        customer: 42,
+        region: "eu".into(),
+        date: 20210101,
+    };
+
+    assert_eq!(key.encode(), (42u64, "eu".to_owned(), 20210101u32).encode());
+    assert_eq!(
+        OrderKey::try_decode(&key.encode()).unwrap(),
+        (key.clone(), &[][..])
+    );
+
+    assert!(matches!(
+        OrderKey::try_decode(&key.encode()[..12]),
+        Err(OrderKeyDecodeError::DecodeRegionError(_))
+    ));
+
+    let wrapper = Wrapper(key.clone());
+    assert_eq!(wrapper.encode(), key.encode());
+    assert_eq!(Wrapper::try_decode(&wrapper.encode()).unwrap().0, wrapper);
+}
+
+#[test]
+fn derive_enum_key() {
+    let keys = [
+        Priority::Low,
+        Priority::Normal(0),
+        Priority::Normal(7),
+        Priority::High {
+            level: -3,
+            escalated: 0,
+        },
+        Priority::High {
+            level: 2,
+            escalated: 1,
+        },
+    ];
+
+    for window in keys.windows(2) {
+        assert!(window[0] < window[1]);
+        assert!(window[0].encode() < window[1].encode());
+    }
+
+    for key in keys.iter() {
+        assert_eq!(
+            Priority::try_decode(&key.encode()).unwrap(),
+            (*key, &[][..])
+        );
+    }
+
+    assert!(matches!(
+        Priority::try_decode(&[3]),
+        Err(PriorityDecodeError::UnknownVariant(3))
+    ));
+    assert!(matches!(
+        Priority::try_decode(&[]),
+        Err(PriorityDecodeError::DataTooShort(_))
+    ));
+    assert!(matches!(
+        Priority::try_decode(&[2, 0x80]),
+        Err(PriorityDecodeError::DecodeHighLevelError(_))
+    ));
+}
+
+#[test]
+fn derive_struct_key_prefix() {
+    let key = OrderKey {
+        customer: 42,
+        region: "eu".into(),
+        date: 20210101,
+    };
+
+    let prefix = OrderKeyPrefix::customer(42);
+    assert_eq!(prefix.encode(), (42u64,).encode());
+
+    let prefix = prefix.region("eu".into());
+    assert_eq!(prefix.encode(), (42u64, "eu".to_owned()).encode());
+
+    let prefix = prefix.date(key.date);
+    assert_eq!(prefix.encode(), key.encode());
+}
+
+#[test]
+fn scan_struct_key_prefix() {
+    let mut store = BTreeMap::new();
+
+    for (customer, region, date) in [(1, "eu", 1), (1, "eu", 2), (1, "us", 1), (2, "eu", 1)] {
+        Order(OrderKey {
+            customer,
+            region: region.into(),
+            date,
+        })
+        .persist(&mut store)
+        .unwrap();
+    }
+
+    let prefix = OrderKeyPrefix::customer(1);
+    assert_eq!(Order::scan_prefix(&store, &prefix).count(), 3);
+
+    let prefix = prefix.region("eu".into());
+    assert_eq!(Order::scan_prefix(&store, &prefix).count(), 2);
+}
+
+#[cfg(feature = "ordered")]
+#[test]
+fn ordered_string_key() {
+    use bobsled::Ordered;
+
+    let mut strings = ["", "a", "a\0", "a\0b", "ab", "b", "ba", "\u{ff}"];
+    strings.sort_unstable();
+
+    let encoded = strings
+        .iter()
+        .map(|s| Ordered(s.to_string()).encode())
+        .collect::<Vec<_>>();
+
+    for window in encoded.windows(2) {
+        assert!(window[0] < window[1]);
+    }
+
+    for (string, bytes) in strings.iter().zip(encoded.iter()) {
+        assert_eq!(bytes, &Ordered(*string).encode());
+
+        let bytes = (Ordered(string.to_string()), 7u8).encode();
+        let ((Ordered(decoded), n), rest) = <(Ordered<String>, u8)>::try_decode(&bytes).unwrap();
+        assert_eq!((decoded.as_str(), n, rest), (*string, 7, &[][..]));
+    }
+
+    assert!(Ordered::<String>::try_decode(b"abc").is_err());
+    assert!(Ordered::<String>::try_decode(b"a\0\x02").is_err());
+}
+
+#[cfg(feature = "ordered")]
+#[test]
+fn ordered_string_range() {
+    use bobsled::Ordered;
+
+    #[derive(Debug, Clone, PartialEq, Record)]
+    struct Tag(#[bobsled(key)] Ordered<String>);
+
+    let mut store = BTreeMap::new();
+    for tag in ["a", "aa", "ab", "b", "ba", "c", "zzz"] {
+        Tag(Ordered(tag.into())).persist(&mut store).unwrap();
+    }
+
+    let tags = Tag::scan_range(&store, Ordered("a")..Ordered("b"))
+        .map(|tag| tag.unwrap().0 .0)
+        .collect::<Vec<_>>();
+    assert_eq!(tags, ["a", "aa", "ab"]);
+
+    assert_eq!(
+        Tag::scan_range(&store, Ordered("aa")..=Ordered("c")).count(),
+        5
+    );
+}
+
+#[test]
+fn portable_length_header() {
+    let mut bytes = 5u64.to_be_bytes().to_vec();
+    bytes.extend_from_slice(b"hello");
+
+    assert_eq!("hello".encode(), bytes);
+    assert_eq!(
+        String::try_decode(&bytes).unwrap(),
+        ("hello".to_owned(), &[][..])
+    );
+
+    let mut bytes = 2u64.to_be_bytes().to_vec();
+    bytes.extend_from_slice(&[0, 1, 0, 2]);
+
+    assert_eq!(vec![1u16, 2u16].encode(), bytes);
+    assert_eq!(
+        Vec::<u16>::try_decode(&bytes).unwrap(),
+        (vec![1, 2], &[][..])
+    );
+
+    assert!(String::try_decode(&u64::MAX.to_be_bytes()).is_err());
+    assert!(Vec::<u8>::try_decode(&u64::MAX.to_be_bytes()).is_err());
+}
+
+#[cfg(feature = "ordered")]
+#[test]
+fn str_prefix_scan() {
+    use bobsled::{Ordered, StrPrefix};
+
+    #[derive(Debug, Clone, PartialEq, Record)]
+    struct Login(
+        #[bobsled(key)] u64,
+        #[bobsled(key)] Ordered<String>,
+        #[bobsled(key)] u64,
+    );
+
+    let mut store = BTreeMap::new();
+    for (user, name, n) in [
+        (1, "a", 0),
+        (1, "ab", 0),
+        (1, "abc", 1),
+        (1, "b", 0),
+        (2, "ab", 0),
+    ] {
+        Login(user, Ordered(name.into()), n)
+            .persist(&mut store)
+            .unwrap();
+    }
+
+    assert_eq!(
+        Login::scan_prefix(&store, &(1u64, StrPrefix("ab"))).count(),
+        2
+    );
+    assert_eq!(
+        Login::scan_prefix(&store, &(1u64, StrPrefix("a"))).count(),
+        3
+    );
+    assert_eq!(
+        Login::scan_prefix(&store, &(1u64, StrPrefix(""))).count(),
+        4
+    );
+    assert_eq!(
+        Login::scan_prefix(&store, &(2u64, StrPrefix("b"))).count(),
+        0
+    );
+
+    #[derive(Debug, Clone, PartialEq, Record)]
+    struct Name(#[bobsled(key)] Ordered<String>, #[bobsled(key)] u64);
+
+    let mut store = BTreeMap::new();
+    for name in ["a", "ab", "abc", "b"] {
+        Name(Ordered(name.into()), 0).persist(&mut store).unwrap();
+    }
+
+    assert_eq!(Name::scan_prefix(&store, &StrPrefix("ab")).count(), 2);
+}
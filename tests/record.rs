@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use bobsled::{Index, Record};
+
+#[derive(Debug, Clone, PartialEq, Record)]
+struct User {
+    #[bobsled(key)]
+    pub org: String,
+    #[bobsled(key)]
+    pub id: u64,
+    pub name: String,
+    pub scores: Vec<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Record)]
+struct Pair(#[bobsled(key)] u32, Option<String>);
+
+#[test]
+fn derive_record() {
+    let mut store = BTreeMap::new();
+
+    let user = User {
+        org: "bobsled".into(),
+        id: 7,
+        name: "Bob".into(),
+        scores: vec![1.0, 2.5],
+    };
+    user.persist(&mut store).unwrap();
+
+    let (key, _) = user.try_encode().unwrap();
+    assert_eq!(key, ("bobsled".to_owned(), 7));
+    assert_eq!(Some(user.clone()), User::fetch(&store, &key).unwrap());
+
+    let users = User::scan_prefix(&store, &("bobsled".to_owned(),))
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Iterating should succeed");
+    assert_eq!(users, vec![user]);
+
+    let pair = Pair(3, Some("three".into()));
+    pair.persist(&mut store).unwrap();
+
+    assert_eq!(Some(pair), Pair::fetch(&store, &(3,)).unwrap());
+}
+
+#[test]
+fn derive_record_decode_error() {
+    assert!(matches!(
+        User::try_decode(("bobsled".into(), 7), &[]),
+        Err(UserDecodeError::DecodeNameError(_))
+    ));
+
+    // A corrupt length header fails to decode instead of allocating
+    assert!(matches!(
+        User::try_decode(("bobsled".into(), 7), &[0xFF; 8]),
+        Err(UserDecodeError::DecodeNameError(_))
+    ));
+}
+
+#[derive(Debug, Clone, PartialEq, Record)]
+#[bobsled(table = "acct")]
+struct Account {
+    #[bobsled(key)]
+    id: u64,
+    balance: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Record)]
+#[bobsled(table = b"sess")]
+struct Session {
+    #[bobsled(key)]
+    id: u64,
+    token: String,
+}
+
+#[test]
+fn derive_record_table() {
+    let mut store = BTreeMap::new();
+
+    let account = Account { id: 1, balance: 10 };
+    let session = Session {
+        id: 1,
+        token: "abc".into(),
+    };
+    account.persist(&mut store).unwrap();
+    session.persist(&mut store).unwrap();
+    Pair(1, None).persist(&mut store).unwrap();
+
+    assert_eq!(Account::TABLE, b"acct");
+    assert!(store.contains_key(&[&b"acct"[..], &1u64.to_be_bytes()].concat()));
+
+    assert_eq!(
+        Account::fetch(&store, &(1,)).unwrap(),
+        Some(account.clone())
+    );
+    assert_eq!(Session::fetch(&store, &(1,)).unwrap(), Some(session));
+
+    let accounts = Account::scan(&store)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(accounts, vec![account]);
+    assert_eq!(Session::scan(&store).count(), 1);
+    assert_eq!(Session::count_range(&store, (0u64,)..).unwrap(), 1);
+
+    Account::remove(&mut store, &(1,)).unwrap();
+    assert_eq!(Account::scan(&store).count(), 0);
+    assert_eq!(Session::scan(&store).count(), 1);
+}
+
+#[derive(Debug, Clone, PartialEq, Record)]
+#[bobsled(table = "memb", index(MemberName))]
+struct Member {
+    #[bobsled(key)]
+    id: u64,
+    name: String,
+}
+
+struct MemberName;
+
+impl Index<Member> for MemberName {
+    type Key = String;
+
+    const TABLE: &'static [u8] = b"mnam";
+
+    fn key(record: &Member) -> Self::Key {
+        record.name.clone()
+    }
+}
+
+#[test]
+fn derive_record_index() {
+    let mut store = BTreeMap::new();
+
+    let named = |store: &BTreeMap<Vec<u8>, Vec<u8>>, name: &str| {
+        Member::scan_index::<MemberName, _>(store, &name.to_owned())
+            .map(|r| r.unwrap().id)
+            .collect::<Vec<_>>()
+    };
+
+    let ann = Member {
+        id: 1,
+        name: "Ann".into(),
+    };
+    ann.create(&mut store).unwrap();
+    Member {
+        id: 2,
+        name: "Bob".into(),
+    }
+    .replace(&mut store)
+    .unwrap();
+    assert_eq!(named(&store, "Ann"), [1]);
+    assert_eq!(named(&store, "Bob"), [2]);
+
+    let renamed = Member {
+        id: 1,
+        name: "Anna".into(),
+    };
+    Member::compare_and_swap(&mut store, &(1,), Some(&ann), Some(&renamed))
+        .unwrap()
+        .unwrap();
+    assert_eq!(named(&store, "Ann"), Vec::<u64>::new());
+    assert_eq!(named(&store, "Anna"), [1]);
+
+    Member::take(&mut store, &(2,)).unwrap();
+    assert_eq!(named(&store, "Bob"), Vec::<u64>::new());
+    assert_eq!(store.len(), 2);
+}
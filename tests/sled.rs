@@ -0,0 +1,483 @@
+#![cfg(feature = "sled")]
+
+use std::{convert::Infallible, str::Utf8Error};
+
+use bobsled::{Index, Record};
+use sled::transaction::TransactionError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TestRecord {
+    pub id: u64,
+    pub data: String,
+}
+
+impl Record for TestRecord {
+    type Key = u64;
+    type EncodeError = Infallible;
+    type DecodeError = Utf8Error;
+
+    fn try_encode(&self) -> Result<(Self::Key, Vec<u8>), Self::EncodeError> {
+        Ok((self.id, self.data.as_bytes().into()))
+    }
+
+    fn try_decode(key: Self::Key, value: &[u8]) -> Result<Self, Self::DecodeError> {
+        Ok(Self {
+            id: key,
+            data: std::str::from_utf8(value)?.into(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Record)]
+#[bobsled(table = "l", index(Target))]
+struct Label(#[bobsled(key)] String, u64);
+
+struct Target;
+
+impl Index<Label> for Target {
+    type Key = u64;
+
+    const TABLE: &'static [u8] = b"t";
+
+    fn key(record: &Label) -> Self::Key {
+        record.1
+    }
+}
+
+fn open() -> sled::Tree {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+
+    db.open_tree("test").unwrap()
+}
+
+#[test]
+fn tree() {
+    let tree = open();
+
+    for id in 0..4 {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&tree).unwrap();
+    }
+
+    assert_eq!(
+        TestRecord::fetch(&tree, &2).unwrap().map(|r| r.data),
+        Some("Record 2".into())
+    );
+    assert_eq!(TestRecord::scan(&tree).count(), 4);
+    assert_eq!(TestRecord::scan_range(&tree, 1u64..=2u64).count(), 2);
+    assert_eq!(TestRecord::count_range(&tree, 1u64..=2u64).unwrap(), 2);
+    assert!(TestRecord::exists(&tree, &2).unwrap());
+
+    TestRecord::remove(&tree, &2).unwrap();
+    assert_eq!(TestRecord::fetch(&tree, &2).unwrap(), None);
+}
+
+#[test]
+fn transactional_tree() {
+    let tree = open();
+
+    let result: Result<_, TransactionError<Infallible>> = tree.transaction(|tx| {
+        let record = TestRecord {
+            id: 1,
+            data: "Hello there!".into(),
+        };
+        record.persist(tx).unwrap();
+
+        Ok(TestRecord::fetch(tx, &1).unwrap())
+    });
+
+    assert_eq!(result.unwrap().map(|r| r.id), Some(1));
+    assert_eq!(TestRecord::scan(&tree).count(), 1);
+}
+
+#[test]
+fn reverse_scan() {
+    let tree = open();
+
+    for id in 0..10 {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&tree).unwrap();
+    }
+
+    let latest = TestRecord::scan_range_rev(&tree, 5u64..)
+        .take(2)
+        .map(|r| r.unwrap().id)
+        .collect::<Vec<_>>();
+    assert_eq!(latest, [9, 8]);
+}
+
+#[test]
+fn update() {
+    let tree = open();
+
+    let threads = (0..4)
+        .map(|_| {
+            let tree = tree.clone();
+
+            std::thread::spawn(move || {
+                for _ in 0..25 {
+                    TestRecord::update(&tree, &0, |record| {
+                        let count = record.map_or(0, |r| r.data.parse::<u32>().unwrap());
+
+                        Some(TestRecord {
+                            id: 0,
+                            data: (count + 1).to_string(),
+                        })
+                    })
+                    .unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(
+        TestRecord::fetch(&tree, &0).unwrap().map(|r| r.data),
+        Some("100".into())
+    );
+}
+
+#[test]
+fn compare_and_swap() {
+    use bobsled::{CompareAndSwapError, ReadStoreError, WriteStoreError};
+
+    let tree = open();
+
+    let stock = |count: u32| TestRecord {
+        id: 0,
+        data: count.to_string(),
+    };
+
+    TestRecord::compare_and_swap(&tree, &0, None, Some(&stock(10)))
+        .unwrap()
+        .unwrap();
+    TestRecord::compare_and_swap(&tree, &0, Some(&stock(10)), Some(&stock(9)))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        TestRecord::compare_and_swap(&tree, &0, Some(&stock(10)), Some(&stock(8))).unwrap(),
+        Err(CompareAndSwapError {
+            current: Some(stock(9))
+        })
+    );
+
+    TestRecord::compare_and_swap(&tree, &0, Some(&stock(9)), None)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        TestRecord::compare_and_swap(&tree, &0, Some(&stock(9)), None).unwrap(),
+        Err(CompareAndSwapError { current: None })
+    );
+    // The new record must belong under the same key
+    let elsewhere = TestRecord {
+        id: 1,
+        data: "1".into(),
+    };
+    assert_eq!(
+        TestRecord::compare_and_swap(&tree, &0, None, Some(&elsewhere)),
+        Err(ReadStoreError::StoreError(WriteStoreError::KeyMismatch))
+    );
+    assert_eq!(tree.len(), 0);
+}
+
+#[test]
+fn create() {
+    use bobsled::CreateStoreError;
+
+    let tree = open();
+
+    let created = (0..4)
+        .map(|worker| {
+            let tree = tree.clone();
+
+            std::thread::spawn(move || {
+                TestRecord {
+                    id: 0,
+                    data: format!("Worker {}", worker),
+                }
+                .create(&tree)
+            })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(created.iter().filter(|result| result.is_ok()).count(), 1);
+    assert!(created
+        .iter()
+        .all(|result| matches!(result, Ok(()) | Err(CreateStoreError::AlreadyExists))));
+}
+
+#[test]
+fn remove_range() {
+    let tree = open();
+
+    for id in 0..10 {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&tree).unwrap();
+    }
+
+    assert_eq!(TestRecord::remove_range(&tree, 2u64..5u64).unwrap(), 3);
+    assert_eq!(TestRecord::remove_prefix(&tree, &9u64).unwrap(), 1);
+    assert_eq!(TestRecord::scan(&tree).count(), 6);
+}
+
+#[test]
+fn record_batch() {
+    use bobsled::RecordBatch;
+
+    let tree = open();
+
+    let mut batch = RecordBatch::new();
+    for id in 0..3 {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&mut batch).unwrap();
+    }
+    TestRecord::remove(&mut batch, &1).unwrap();
+
+    batch.apply(&tree).unwrap().unwrap();
+
+    let ids = TestRecord::scan_keys(&tree)
+        .map(|key| key.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [0, 2]);
+}
+
+#[test]
+fn tables_transaction() {
+    use bobsled::{Tables, TransactionError, UnregisteredTable};
+
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let tables = Tables::new()
+        .with::<TestRecord>(db.open_tree("records").unwrap())
+        .with::<Label>(db.open_tree("labels").unwrap());
+
+    let result: Result<_, TransactionError<Infallible>> = tables.transaction(|tx| {
+        let record = TestRecord {
+            id: 7,
+            data: "Hello there!".into(),
+        };
+        record.persist(tx.table::<TestRecord>()?)?;
+        Label("greeting".into(), record.id).persist(tx.table::<Label>()?)?;
+
+        Ok(Label::fetch(
+            tx.table::<Label>()?,
+            &("greeting".to_owned(),),
+        )?)
+    });
+    assert_eq!(result.unwrap(), Some(Label("greeting".into(), 7)));
+
+    let labels = tables.tree::<Label>().unwrap();
+    assert_eq!(Label::scan(labels).count(), 1);
+    assert_eq!(
+        TestRecord::fetch(tables.tree::<TestRecord>().unwrap(), &7)
+            .unwrap()
+            .map(|r| r.data),
+        Some("Hello there!".into())
+    );
+
+    let result: Result<(), _> = tables.transaction(|tx| {
+        Label::remove(tx.table::<Label>()?, &("greeting".to_owned(),))?;
+        TestRecord::remove(tx.table::<TestRecord>()?, &7)?;
+
+        Err(TransactionError::Abort("changed my mind"))
+    });
+    assert!(matches!(
+        result,
+        Err(TransactionError::Abort("changed my mind"))
+    ));
+
+    assert_eq!(Label::scan(labels).count(), 1);
+    assert!(TestRecord::exists(tables.tree::<TestRecord>().unwrap(), &7).unwrap());
+
+    let result: Result<_, TransactionError<Infallible>> = Tables::new().transaction(|tx| {
+        TestRecord::exists(tx.table::<TestRecord>()?, &7)?;
+
+        Ok(())
+    });
+    match result {
+        Err(TransactionError::Record(err)) => assert_eq!(
+            err.downcast_ref::<UnregisteredTable>(),
+            Some(&UnregisteredTable {
+                record: std::any::type_name::<TestRecord>()
+            })
+        ),
+        result => panic!("Expected an unregistered table, got {:?}", result),
+    }
+}
+
+#[test]
+fn secondary_index() {
+    let tree = open();
+    let labels = |target: u64| {
+        Label::scan_index::<Target, _>(&tree, &target)
+            .map(|r| r.unwrap().0)
+            .collect::<Vec<_>>()
+    };
+
+    Label("first".into(), 1).persist(&tree).unwrap();
+    Label("one".into(), 1).persist(&tree).unwrap();
+    Label("second".into(), 2).persist(&tree).unwrap();
+    assert_eq!(labels(1), ["one", "first"]);
+
+    let result: Result<(), TransactionError<Infallible>> = tree.transaction(|tx| {
+        Label("first".into(), 2).persist(tx).unwrap();
+        Label::remove(tx, &("one".to_owned(),)).unwrap();
+
+        Ok(())
+    });
+    result.unwrap();
+
+    assert_eq!(labels(1), Vec::<String>::new());
+    assert_eq!(labels(2), ["first", "second"]);
+    assert_eq!(tree.len(), 4);
+
+    // Batches remove the entries of the records they replace once they're applied
+    let mut batch = bobsled::RecordBatch::new();
+    Label("second".into(), 3).persist(&mut batch).unwrap();
+    batch.apply(&tree).unwrap().unwrap();
+
+    assert_eq!(labels(2), ["first"]);
+    assert_eq!(labels(3), ["second"]);
+    assert_eq!(tree.len(), 4);
+
+    // Removing a range of records removes their index entries too
+    assert_eq!(
+        Label::remove_range(&tree, ("first".to_owned(),)..=("second".to_owned(),)).unwrap(),
+        2
+    );
+    assert_eq!(labels(2), Vec::<String>::new());
+    assert_eq!(labels(3), Vec::<String>::new());
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn unique_index() {
+    use bobsled::{Index, IndexDef, RecordBatch, UniqueViolation, WriteStoreError};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Handle(u64, String);
+
+    impl Record for Handle {
+        type Key = u64;
+        type EncodeError = Infallible;
+        type DecodeError = Utf8Error;
+
+        const TABLE: &'static [u8] = b"h";
+
+        fn try_encode(&self) -> Result<(Self::Key, Vec<u8>), Self::EncodeError> {
+            Ok((self.0, self.1.as_bytes().into()))
+        }
+
+        fn try_decode(key: Self::Key, value: &[u8]) -> Result<Self, Self::DecodeError> {
+            Ok(Handle(key, std::str::from_utf8(value)?.into()))
+        }
+
+        fn indexes() -> Vec<IndexDef<Self>> {
+            vec![IndexDef::of::<Name>()]
+        }
+    }
+
+    struct Name;
+
+    impl Index<Handle> for Name {
+        type Key = String;
+
+        const TABLE: &'static [u8] = b"n";
+        const UNIQUE: bool = true;
+
+        fn key(record: &Handle) -> Self::Key {
+            record.1.clone()
+        }
+    }
+
+    let tree = open();
+    Handle(1, "bob".into()).persist(&tree).unwrap();
+
+    assert_eq!(
+        Handle(2, "bob".into()).persist(&tree),
+        Err(WriteStoreError::UniqueViolation(UniqueViolation {
+            index: b"n"
+        }))
+    );
+    assert_eq!(Handle::fetch(&tree, &2).unwrap(), None);
+
+    let result: Result<(), TransactionError<Infallible>> = tree.transaction(|tx| {
+        Handle(1, "robert".into()).persist(tx).unwrap();
+        Handle(2, "bob".into()).persist(tx).unwrap();
+
+        Ok(())
+    });
+    result.unwrap();
+
+    let owner = Handle::scan_index::<Name, _>(&tree, &"bob".to_owned())
+        .map(|r| r.unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(owner, [2]);
+
+    // Record batches are checked when they're applied
+    let mut batch = RecordBatch::new();
+    Handle(3, "bob".into()).persist(&mut batch).unwrap();
+    assert_eq!(
+        batch.apply(&tree).unwrap(),
+        Err(UniqueViolation { index: b"n" })
+    );
+    assert_eq!(Handle::fetch(&tree, &3).unwrap(), None);
+
+    // Sled batches can't be checked at all
+    let mut batch = sled::Batch::default();
+    assert_eq!(
+        Handle(3, "bob".into()).persist(&mut batch),
+        Err(WriteStoreError::IndexUnsupported)
+    );
+}
+
+#[test]
+fn insert_auto() {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let tree = db.open_tree("records").unwrap();
+
+    // A plain tree is paired with its database, which generates the ids
+    let first = TestRecord::insert_auto((&db, &tree), |id| TestRecord {
+        id,
+        data: "Hello there!".into(),
+    })
+    .unwrap();
+
+    let result: Result<_, TransactionError<Infallible>> = tree.transaction(|tx| {
+        let record = TestRecord::insert_auto(tx, |id| TestRecord {
+            id,
+            data: "General Kenobi!".into(),
+        })
+        .unwrap();
+
+        Ok(record)
+    });
+    let second = result.unwrap();
+    assert!(second.id > first.id);
+
+    // Ids are generated by the database, so nothing but the records is stored in the tree
+    assert_eq!(
+        TestRecord::scan(&tree)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        [first, second]
+    );
+}
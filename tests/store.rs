@@ -1,6 +1,14 @@
-use std::{collections::BTreeMap, convert::Infallible, str::Utf8Error};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    ops::Bound,
+    str::Utf8Error,
+};
 
-use bobsled::Record;
+use bobsled::{
+    Cursor, RawScanStore, RawStore, RawWriteStore, ReadStoreError, Record, RecordBatch,
+    UniqueViolation,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct TestRecord {
@@ -56,3 +64,818 @@ fn test() {
     let n_records = TestRecord::scan_range(&store, 0u64..1u64).count();
     assert_eq!(n_records, 1);
 }
+
+#[test]
+fn hash_map() {
+    let mut store = HashMap::new();
+
+    let record = TestRecord {
+        id: 0,
+        data: "Hello there!".into(),
+    };
+    record.persist(&mut store).unwrap();
+
+    assert_eq!(Ok(Some(record)), TestRecord::fetch(&store, &0));
+
+    TestRecord::remove(&mut store, &0).unwrap();
+    assert_eq!(Ok(None), TestRecord::fetch(&store, &0));
+}
+
+/// A minimal custom backend, kept sorted by key
+#[derive(Default)]
+struct SortedVec(Vec<(Vec<u8>, Vec<u8>)>);
+
+impl<'a> RawStore for &'a SortedVec {
+    type Error = Infallible;
+    type Value = &'a [u8];
+
+    fn get(self, key: &[u8]) -> Result<Option<Self::Value>, Self::Error> {
+        Ok(self
+            .0
+            .binary_search_by(|(k, _)| k.as_slice().cmp(key))
+            .ok()
+            .map(|i| self.0[i].1.as_slice()))
+    }
+}
+
+impl<'a> RawScanStore for &'a SortedVec {
+    type Key = &'a [u8];
+    type Range = std::vec::IntoIter<Result<(&'a [u8], &'a [u8]), Infallible>>;
+
+    fn range(self, range: (Bound<&[u8]>, Bound<&[u8]>)) -> Self::Range {
+        self.0
+            .iter()
+            .filter(|(k, _)| std::ops::RangeBounds::contains(&range, k.as_slice()))
+            .map(|(k, v)| Ok((k.as_slice(), v.as_slice())))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl RawWriteStore for &mut SortedVec {
+    type Error = Infallible;
+
+    fn insert(self, key: &[u8], value: Vec<u8>) -> Result<(), Self::Error> {
+        match self.0.binary_search_by(|(k, _)| k.as_slice().cmp(key)) {
+            Ok(i) => self.0[i].1 = value,
+            Err(i) => self.0.insert(i, (key.into(), value)),
+        }
+
+        Ok(())
+    }
+
+    fn remove(self, key: &[u8]) -> Result<(), Self::Error> {
+        if let Ok(i) = self.0.binary_search_by(|(k, _)| k.as_slice().cmp(key)) {
+            self.0.remove(i);
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn custom_raw_store() {
+    let mut store = SortedVec::default();
+
+    for id in (0..5).rev() {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&mut store).unwrap();
+    }
+
+    assert_eq!(
+        TestRecord::fetch(&store, &3).unwrap().map(|r| r.data),
+        Some("Record 3".into())
+    );
+
+    let ids = TestRecord::scan_range(&store, 1u64..=3u64)
+        .map(|r| r.unwrap().id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [1, 2, 3]);
+
+    TestRecord::remove(&mut store, &3).unwrap();
+    assert_eq!(TestRecord::scan(&store).count(), 4);
+}
+
+#[test]
+fn reverse_scan() {
+    let mut store = BTreeMap::new();
+
+    for id in 0..10 {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&mut store).unwrap();
+    }
+
+    let latest = TestRecord::scan_range_rev(&store, 2u64..8u64)
+        .take(3)
+        .map(|r| r.unwrap().id)
+        .collect::<Vec<_>>();
+    assert_eq!(latest, [7, 6, 5]);
+
+    let mut iter = TestRecord::scan(&store);
+    assert_eq!(iter.next().unwrap().unwrap().id, 0);
+    assert_eq!(iter.next_back().unwrap().unwrap().id, 9);
+}
+
+#[test]
+fn pagination() {
+    let mut store = BTreeMap::new();
+
+    for id in 0..10 {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&mut store).unwrap();
+    }
+
+    let mut pages = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = TestRecord::scan_range_page(&store, 2u64..9u64, cursor.as_ref(), 3).unwrap();
+        pages.push(page.records.iter().map(|r| r.id).collect::<Vec<_>>());
+
+        cursor = page.next;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(pages, [vec![2, 3, 4], vec![5, 6, 7], vec![8]]);
+
+    let page = TestRecord::scan_prefix_page(&store, &4u64, None, 3).unwrap();
+    assert_eq!(page.records.len(), 1);
+    assert_eq!(page.next, None);
+
+    // A full last page shouldn't produce a cursor to an empty page
+    let page = TestRecord::scan_range_page(&store, 0u64..3u64, None, 3).unwrap();
+    assert_eq!(page.records.len(), 3);
+    assert_eq!(page.next, None);
+
+    // An empty page would hide the records after it
+    assert!(matches!(
+        TestRecord::scan_range_page(&store, 0u64..5u64, None, 0),
+        Err(ReadStoreError::ZeroLimit)
+    ));
+
+    // Cursors come from clients, so one past the end of the range just produces an empty page
+    for after in [4u64, 50u64] {
+        let cursor = Cursor::from_bytes(after.to_be_bytes().to_vec());
+        let page = TestRecord::scan_range_page(&store, 0u64..5u64, Some(&cursor), 3).unwrap();
+        assert!(page.records.is_empty());
+        assert_eq!(page.next, None);
+    }
+
+    let cursor = Cursor::from_bytes(50u64.to_be_bytes().to_vec());
+    let page = TestRecord::scan_range_page(&store, 0u64..=5u64, Some(&cursor), 3).unwrap();
+    assert!(page.records.is_empty());
+}
+
+#[cfg(feature = "base64")]
+#[test]
+fn base64_cursor() {
+    let cursor = Cursor::from_bytes(vec![0, 1, 0xFF, 0xFE]);
+    let encoded = cursor.to_base64();
+
+    assert!(encoded
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+    assert_eq!(Cursor::from_base64(encoded).unwrap(), cursor);
+}
+
+#[test]
+fn scan_keys() {
+    let mut store = BTreeMap::new();
+
+    for id in 0..5 {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&mut store).unwrap();
+    }
+
+    // Values that don't decode don't matter when only scanning keys
+    store.insert(5u64.to_be_bytes().to_vec(), vec![0xFF]);
+
+    assert!(TestRecord::scan(&store).any(|r| r.is_err()));
+
+    let ids = TestRecord::scan_keys(&store)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(ids, [0, 1, 2, 3, 4, 5]);
+
+    let ids = TestRecord::scan_keys_range(&store, 3u64..)
+        .rev()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(ids, [5, 4, 3]);
+
+    assert_eq!(TestRecord::scan_keys_prefix(&store, &2u64).count(), 1);
+}
+
+#[test]
+fn count_exists_size() {
+    let mut store = BTreeMap::new();
+
+    for id in 0..10 {
+        let record = TestRecord {
+            id,
+            data: "1234".into(),
+        };
+        record.persist(&mut store).unwrap();
+    }
+
+    assert_eq!(TestRecord::exists(&store, &3), Ok(true));
+    assert_eq!(TestRecord::exists(&store, &10), Ok(false));
+
+    assert_eq!(TestRecord::count_range(&store, 2u64..5u64), Ok(3));
+    assert_eq!(TestRecord::count_range(&store, 2u64..=5u64), Ok(4));
+    assert_eq!(TestRecord::count_prefix(&store, &7u64), Ok(1));
+
+    // 8 bytes of key and 4 bytes of value per record
+    assert_eq!(TestRecord::size_range(&store, 0u64..5u64), Ok(5 * 12));
+    assert_eq!(TestRecord::size_prefix(&store, &7u64), Ok(12));
+}
+
+#[test]
+fn update() {
+    let mut store = BTreeMap::new();
+
+    let append = |record: Option<TestRecord>| {
+        let mut record = record.unwrap_or(TestRecord {
+            id: 0,
+            data: String::new(),
+        });
+        record.data.push('!');
+
+        Some(record)
+    };
+
+    let record = TestRecord::update(&mut store, &0, append).unwrap();
+    assert_eq!(record.map(|r| r.data), Some("!".into()));
+
+    TestRecord::update(&mut store, &0, append).unwrap();
+    assert_eq!(
+        TestRecord::fetch(&store, &0).unwrap().map(|r| r.data),
+        Some("!!".into())
+    );
+
+    // Moving the record to another key isn't allowed
+    let moved = TestRecord::update(&mut store, &0, |record| {
+        record.map(|record| TestRecord { id: 1, ..record })
+    });
+    assert!(matches!(
+        moved,
+        Err(bobsled::ReadStoreError::StoreError(
+            bobsled::WriteStoreError::KeyMismatch
+        ))
+    ));
+    assert!(!TestRecord::exists(&store, &1).unwrap());
+
+    assert_eq!(TestRecord::update(&mut store, &0, |_| None).unwrap(), None);
+    assert!(!TestRecord::exists(&store, &0).unwrap());
+}
+
+#[test]
+fn replace_take() {
+    let mut store = BTreeMap::new();
+
+    let first = TestRecord {
+        id: 0,
+        data: "first".into(),
+    };
+    let second = TestRecord {
+        id: 0,
+        data: "second".into(),
+    };
+
+    assert_eq!(first.replace(&mut store).unwrap(), None);
+    assert_eq!(second.replace(&mut store).unwrap(), Some(first));
+
+    assert_eq!(TestRecord::take(&mut store, &0).unwrap(), Some(second));
+    assert_eq!(TestRecord::take(&mut store, &0).unwrap(), None);
+}
+
+#[test]
+fn create() {
+    use bobsled::CreateStoreError;
+
+    let mut store = BTreeMap::new();
+
+    let record = TestRecord {
+        id: 0,
+        data: "first".into(),
+    };
+    record.create(&mut store).unwrap();
+
+    let duplicate = TestRecord {
+        id: 0,
+        data: "second".into(),
+    };
+    assert_eq!(
+        duplicate.create(&mut store),
+        Err(CreateStoreError::AlreadyExists)
+    );
+    assert_eq!(TestRecord::fetch(&store, &0).unwrap(), Some(record));
+}
+
+#[test]
+fn remove_range() {
+    let mut store = BTreeMap::new();
+
+    for id in 0..10 {
+        let record = TestRecord {
+            id,
+            data: format!("Record {}", id),
+        };
+        record.persist(&mut store).unwrap();
+    }
+
+    assert_eq!(
+        TestRecord::remove_range(&mut store, (Bound::Excluded(2u64), Bound::Included(5u64)))
+            .unwrap(),
+        3
+    );
+    assert_eq!(TestRecord::remove_range(&mut store, 8u64..).unwrap(), 2);
+    assert_eq!(TestRecord::remove_range(&mut store, 3u64..6u64).unwrap(), 0);
+
+    let ids = TestRecord::scan_keys(&store)
+        .map(|key| key.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [0, 1, 2, 6, 7]);
+
+    assert_eq!(TestRecord::remove_prefix(&mut store, &7u64).unwrap(), 1);
+    assert_eq!(TestRecord::remove_range(&mut store, ..=6u64).unwrap(), 4);
+    assert!(store.is_empty());
+}
+
+#[test]
+fn record_batch() {
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Tag(String);
+
+    impl Record for Tag {
+        type Key = String;
+        type EncodeError = std::fmt::Error;
+        type DecodeError = Infallible;
+
+        fn try_encode(&self) -> Result<(Self::Key, Vec<u8>), Self::EncodeError> {
+            // Rejects tags containing a nul byte, to exercise a failing encode
+            match self.0.find('\0') {
+                Some(_) => Err(std::fmt::Error),
+                None => Ok((self.0.clone(), Vec::new())),
+            }
+        }
+
+        fn try_decode(key: Self::Key, _value: &[u8]) -> Result<Self, Self::DecodeError> {
+            Ok(Tag(key))
+        }
+    }
+
+    let mut store = BTreeMap::new();
+    let stale = TestRecord {
+        id: 1,
+        data: "Stale".into(),
+    };
+    stale.persist(&mut store).unwrap();
+
+    let mut batch = RecordBatch::new();
+
+    let record = TestRecord {
+        id: 0,
+        data: "Hello there!".into(),
+    };
+    record.persist(&mut batch).unwrap();
+    Tag("greeting".into()).persist(&mut batch).unwrap();
+    TestRecord::remove(&mut batch, &1).unwrap();
+
+    assert!(Tag("bad\0".into()).persist(&mut batch).is_err());
+    assert_eq!(batch.len(), 3);
+    assert_eq!(TestRecord::fetch(&store, &0).unwrap(), None);
+
+    batch.apply(&mut store).unwrap().unwrap();
+
+    assert_eq!(TestRecord::fetch(&store, &0).unwrap(), Some(record));
+    assert_eq!(TestRecord::fetch(&store, &1).unwrap(), None);
+    assert_eq!(
+        Tag::fetch(&store, &"greeting".to_owned()).unwrap(),
+        Some(Tag("greeting".into()))
+    );
+}
+
+#[test]
+fn btree_transaction() {
+    use bobsled::{BTreeTransaction, ReplaceStore, WriteStore};
+
+    // Generic over the store, so it could equally run in a sled transaction
+    fn rename<S: Copy + ReplaceStore<TestRecord> + WriteStore<TestRecord>>(store: S, id: u64) {
+        let mut record = TestRecord::take(store, &id).unwrap().unwrap();
+        record.id += 100;
+        record.persist(store).unwrap();
+    }
+
+    let mut store = BTreeMap::new();
+    let record = TestRecord {
+        id: 1,
+        data: "Hello there!".into(),
+    };
+    record.persist(&mut store).unwrap();
+
+    let result = BTreeTransaction::run(&mut store, |tx| {
+        rename(tx, 1);
+
+        assert!(!TestRecord::exists(tx, &1).unwrap());
+        assert!(TestRecord::exists(tx, &101).unwrap());
+
+        Err("abort")
+    });
+    assert_eq!(result, Err::<(), _>("abort"));
+    assert_eq!(TestRecord::fetch(&store, &1).unwrap(), Some(record));
+
+    BTreeTransaction::run(&mut store, |tx| {
+        rename(tx, 1);
+
+        Ok::<_, Infallible>(())
+    })
+    .unwrap();
+
+    let ids = TestRecord::scan_keys(&store)
+        .map(|key| key.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [101]);
+}
+
+#[test]
+fn secondary_index() {
+    use bobsled::{Index, WriteStoreError};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Record)]
+    #[bobsled(table = "user", index(Email))]
+    struct User {
+        #[bobsled(key)]
+        id: u64,
+        email: String,
+    }
+
+    struct Email;
+
+    impl Index<User> for Email {
+        type Key = String;
+
+        const TABLE: &'static [u8] = b"mail";
+
+        fn key(record: &User) -> Self::Key {
+            record.email.clone()
+        }
+    }
+
+    let by_email = |store: &BTreeMap<Vec<u8>, Vec<u8>>, email: &str| {
+        User::scan_index::<Email, _>(store, &email.to_owned())
+            .map(|r| r.unwrap().id)
+            .collect::<Vec<_>>()
+    };
+
+    let mut store = BTreeMap::new();
+    for (id, email) in [
+        (1, "bob@example.com"),
+        (2, "alice@example.com"),
+        (3, "bob@example.org"),
+    ] {
+        User {
+            id,
+            email: email.into(),
+        }
+        .persist(&mut store)
+        .unwrap();
+    }
+
+    assert_eq!(by_email(&store, "bob@example.com"), [1]);
+    assert_eq!(by_email(&store, "alice@example.com"), [2]);
+
+    // Changing the indexed value moves the record's entry
+    User::update(&mut store, &(1,), |user| {
+        user.map(|user| User {
+            email: "robert@example.com".into(),
+            ..user
+        })
+    })
+    .unwrap();
+    assert_eq!(by_email(&store, "bob@example.com"), Vec::<u64>::new());
+    assert_eq!(by_email(&store, "robert@example.com"), [1]);
+
+    User::remove(&mut store, &(3,)).unwrap();
+    assert_eq!(by_email(&store, "bob@example.org"), Vec::<u64>::new());
+    assert_eq!(store.len(), 4);
+
+    // A batch replaces the entries of the records it replaces once it's applied
+    let mut batch = RecordBatch::new();
+    User {
+        id: 2,
+        email: "carol@example.com".into(),
+    }
+    .persist(&mut batch)
+    .unwrap();
+    batch.apply(&mut store).unwrap().unwrap();
+
+    assert_eq!(by_email(&store, "alice@example.com"), Vec::<u64>::new());
+    assert_eq!(by_email(&store, "carol@example.com"), [2]);
+    assert_eq!(store.len(), 4);
+
+    let mut batch = RecordBatch::new();
+    User::remove(&mut batch, &(2,)).unwrap();
+    batch.apply(&mut store).unwrap().unwrap();
+
+    assert_eq!(by_email(&store, "carol@example.com"), Vec::<u64>::new());
+    assert_eq!(store.len(), 2);
+
+    // A store that can only insert and remove can't write index entries, so it writes nothing
+    let mut store = SortedVec::default();
+    let user = User {
+        id: 1,
+        email: "bob@example.com".into(),
+    };
+    assert!(matches!(
+        user.persist(&mut store),
+        Err(WriteStoreError::IndexUnsupported)
+    ));
+    assert!(!User::exists(&store, &(1,)).unwrap());
+}
+
+#[test]
+fn index_without_table() {
+    use bobsled::{CreateStoreError, Index, IndexDef, WriteStoreError};
+
+    // Derived records with indexes must have a table, but hand-written ones can leave it out
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Tagged(u64, String);
+
+    impl Record for Tagged {
+        type Key = u64;
+        type EncodeError = Infallible;
+        type DecodeError = Utf8Error;
+
+        fn try_encode(&self) -> Result<(Self::Key, Vec<u8>), Self::EncodeError> {
+            Ok((self.0, self.1.as_bytes().into()))
+        }
+
+        fn try_decode(key: Self::Key, value: &[u8]) -> Result<Self, Self::DecodeError> {
+            Ok(Self(key, std::str::from_utf8(value)?.into()))
+        }
+
+        fn indexes() -> Vec<IndexDef<Self>> {
+            vec![IndexDef::of::<Tag>()]
+        }
+    }
+
+    struct Tag;
+
+    impl Index<Tagged> for Tag {
+        type Key = String;
+
+        const TABLE: &'static [u8] = b"tag";
+
+        fn key(record: &Tagged) -> Self::Key {
+            record.1.clone()
+        }
+    }
+
+    let mut store = BTreeMap::new();
+    let tagged = Tagged(1, "a".into());
+
+    assert_eq!(tagged.persist(&mut store), Err(WriteStoreError::NoTable));
+    assert_eq!(tagged.create(&mut store), Err(CreateStoreError::NoTable));
+    assert_eq!(
+        Tagged::update(&mut store, &1, |_| Some(tagged.clone())),
+        Err(ReadStoreError::StoreError(WriteStoreError::NoTable))
+    );
+    assert!(store.is_empty());
+}
+
+#[test]
+fn unique_index() {
+    use bobsled::{Index, IndexDef, WriteStoreError};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Account {
+        id: u64,
+        name: String,
+    }
+
+    impl Record for Account {
+        type Key = u64;
+        type EncodeError = Infallible;
+        type DecodeError = Utf8Error;
+
+        const TABLE: &'static [u8] = b"acct";
+
+        fn try_encode(&self) -> Result<(Self::Key, Vec<u8>), Self::EncodeError> {
+            Ok((self.id, self.name.as_bytes().into()))
+        }
+
+        fn try_decode(key: Self::Key, value: &[u8]) -> Result<Self, Self::DecodeError> {
+            Ok(Self {
+                id: key,
+                name: std::str::from_utf8(value)?.into(),
+            })
+        }
+
+        fn indexes() -> Vec<IndexDef<Self>> {
+            vec![IndexDef::of::<Name>()]
+        }
+    }
+
+    struct Name;
+
+    impl Index<Account> for Name {
+        type Key = String;
+
+        const TABLE: &'static [u8] = b"name";
+        const UNIQUE: bool = true;
+
+        fn key(record: &Account) -> Self::Key {
+            record.name.clone()
+        }
+    }
+
+    let account = |id, name: &str| Account {
+        id,
+        name: name.into(),
+    };
+    let violation = UniqueViolation { index: b"name" };
+
+    let mut store = HashMap::new();
+    account(1, "alice").persist(&mut store).unwrap();
+    account(2, "bob").persist(&mut store).unwrap();
+
+    assert_eq!(
+        account(3, "alice").persist(&mut store),
+        Err(WriteStoreError::UniqueViolation(violation))
+    );
+    assert!(!Account::exists(&store, &3).unwrap());
+
+    // Rewriting a record with the same name isn't a violation
+    account(1, "alice").persist(&mut store).unwrap();
+
+    let mut store = BTreeMap::new();
+    account(1, "alice").persist(&mut store).unwrap();
+    account(2, "bob").persist(&mut store).unwrap();
+
+    assert!(matches!(
+        Account::update(&mut store, &2, |_| Some(account(2, "alice"))),
+        Err(bobsled::ReadStoreError::StoreError(
+            WriteStoreError::UniqueViolation(_)
+        ))
+    ));
+    assert_eq!(Account::fetch(&store, &2).unwrap(), Some(account(2, "bob")));
+
+    // Once a name is released, it can be claimed by another record
+    account(1, "carol").persist(&mut store).unwrap();
+    account(2, "alice").persist(&mut store).unwrap();
+
+    let names = Account::scan_index::<Name, _>(&store, &"alice".to_owned())
+        .map(|r| r.unwrap().id)
+        .collect::<Vec<_>>();
+    assert_eq!(names, [2]);
+
+    // Removing a range of records releases their names too
+    Account::remove_range(&mut store, 2u64..=2u64).unwrap();
+    assert_eq!(store.len(), 2);
+    account(3, "alice").create(&mut store).unwrap();
+
+    // Batches are checked against the store and against themselves when they're applied
+    let mut batch = RecordBatch::new();
+    account(4, "alice").persist(&mut batch).unwrap();
+    assert_eq!(batch.apply(&mut store), Ok(Err(violation)));
+
+    let mut batch = RecordBatch::new();
+    account(4, "dave").persist(&mut batch).unwrap();
+    account(5, "dave").persist(&mut batch).unwrap();
+    assert_eq!(batch.apply(&mut store), Ok(Err(violation)));
+    assert_eq!(store.len(), 4);
+
+    // A batch can hand a name over from one record to another
+    let mut batch = RecordBatch::new();
+    account(3, "erin").persist(&mut batch).unwrap();
+    account(4, "alice").persist(&mut batch).unwrap();
+    batch.apply(&mut store).unwrap().unwrap();
+
+    let names = Account::scan_index::<Name, _>(&store, &"alice".to_owned())
+        .map(|r| r.unwrap().id)
+        .collect::<Vec<_>>();
+    assert_eq!(names, [4]);
+
+    // The name can be claimed before the record holding it releases it, as long as it's released by the same batch
+    let mut batch = RecordBatch::new();
+    account(5, "erin").persist(&mut batch).unwrap();
+    account(3, "frank").persist(&mut batch).unwrap();
+    batch.apply(&mut store).unwrap().unwrap();
+
+    let names = Account::scan_index::<Name, _>(&store, &"erin".to_owned())
+        .map(|r| r.unwrap().id)
+        .collect::<Vec<_>>();
+    assert_eq!(names, [5]);
+    assert_eq!(store.len(), 8);
+
+    let mut batch = RecordBatch::new();
+    Account::remove(&mut batch, &5).unwrap();
+    batch.apply(&mut store).unwrap().unwrap();
+    assert_eq!(store.len(), 6);
+}
+
+#[test]
+fn insert_auto() {
+    use bobsled::{
+        BTreeTransaction, CreateStoreError, InsertAutoError, Sequence, SequenceError,
+        SEQUENCE_TABLE,
+    };
+
+    #[derive(Debug, Clone, PartialEq, Eq, Record)]
+    #[bobsled(table = "note")]
+    struct Note {
+        #[bobsled(key)]
+        id: u64,
+        text: String,
+    }
+
+    let mut store = BTreeMap::new();
+
+    for expected in 0..2 {
+        let note = Note::insert_auto(&mut store, |id| Note {
+            id,
+            text: format!("Note {}", id),
+        })
+        .unwrap();
+        assert_eq!(note.id, expected);
+    }
+
+    let id = BTreeTransaction::run(&mut store, |tx| {
+        Note::insert_auto(tx, |id| Note {
+            id,
+            text: format!("Note {}", id),
+        })
+        .map(|note| note.id)
+    })
+    .unwrap();
+    assert_eq!(id, 2);
+
+    // The counter isn't visible to scans
+    let notes = Note::scan(&store)
+        .map(|r| r.unwrap().text)
+        .collect::<Vec<_>>();
+    assert_eq!(notes, ["Note 0", "Note 1", "Note 2"]);
+
+    // Records whose ids were allocated elsewhere are never overwritten
+    let existing = Note {
+        id: 3,
+        text: "Existing".into(),
+    };
+    existing.persist(&mut store).unwrap();
+    assert!(matches!(
+        Note::insert_auto(&mut store, |id| Note {
+            id,
+            text: String::new(),
+        }),
+        Err(InsertAutoError::CreateError(
+            CreateStoreError::AlreadyExists
+        ))
+    ));
+    assert_eq!(Note::fetch(&store, &(3,)).unwrap(), Some(existing));
+
+    // Removing every record doesn't reset the counter
+    assert_eq!(Note::remove_range(&mut store, (0u64,)..), Ok(4));
+    assert_eq!(Sequence::<Note>::next_id(&mut &mut store), Ok(Ok(4)));
+
+    // Records without a table would share their keyspace with the counter
+    assert!(matches!(
+        TestRecord::insert_auto(&mut store, |id| TestRecord {
+            id,
+            data: String::new(),
+        }),
+        Err(InsertAutoError::SequenceError(SequenceError::NoTable))
+    ));
+
+    let counter = [SEQUENCE_TABLE, b"note"].concat();
+
+    store.insert(counter.clone(), vec![1]);
+    assert_eq!(
+        Sequence::<Note>::next_id(&mut &mut store),
+        Ok(Err(SequenceError::InvalidCounter))
+    );
+
+    store.insert(counter, u64::MAX.to_be_bytes().to_vec());
+    let result = BTreeTransaction::run(&mut store, |tx| {
+        Note::insert_auto(tx, |id| Note {
+            id,
+            text: String::new(),
+        })
+    });
+    assert!(matches!(
+        result,
+        Err(InsertAutoError::SequenceError(SequenceError::Exhausted))
+    ));
+}